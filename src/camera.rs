@@ -1 +1,5 @@
+pub(crate) mod frustum;
 pub(crate) mod lightsource;
+pub(crate) mod orbit;
+pub(crate) mod view;
+pub(crate) mod walk;
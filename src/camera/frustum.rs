@@ -0,0 +1,67 @@
+use nalgebra::base::{Matrix4, Vector4};
+
+use crate::camera::view::Camera;
+use crate::linear_algebra::vectors::{
+    add_vec, cross_product, dot_product, mult_vec, normalize_vec,
+};
+
+// matches the near clip in get_triangle_queue and the far plane of the projection
+const NEAR: f32 = 1.;
+const FAR: f32 = 1000.;
+
+// what the camera can see, as six planes facing inwards
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    // normal and distance, a point p is inside when dot(normal, p) + distance >= 0
+    pub planes: [(Vector4<f32>, f32); 6],
+}
+
+impl Frustum {
+    pub fn from_camera(camera: &Camera, projection_matrix: &Matrix4<f32>) -> Self {
+        let (forward, right, up) = (camera.look_dir(), camera.right(), camera.up());
+        let position = camera.position;
+
+        // edges of the view at unit depth
+        let half_width = 1. / projection_matrix[(0, 0)];
+        let half_height = 1. / projection_matrix[(1, 1)];
+        let edge = |side: &Vector4<f32>, extent: f32| add_vec(&forward, &mult_vec(side, extent));
+
+        // normal of the plane through the camera spanned by `a` and `b`, flipped to face forward
+        let side_plane = |a: Vector4<f32>, b: &Vector4<f32>| {
+            let mut normal = normalize_vec(&cross_product(&a, b));
+            if dot_product(&normal, &forward) < 0. {
+                normal = mult_vec(&normal, -1.);
+            }
+            (normal, -dot_product(&normal, &position))
+        };
+
+        let near_point = add_vec(&position, &mult_vec(&forward, NEAR));
+        let far_point = add_vec(&position, &mult_vec(&forward, FAR));
+        let backward = mult_vec(&forward, -1.);
+
+        Frustum {
+            planes: [
+                side_plane(edge(&right, half_width), &up),
+                side_plane(edge(&right, -half_width), &up),
+                side_plane(edge(&up, half_height), &right),
+                side_plane(edge(&up, -half_height), &right),
+                (forward, -dot_product(&forward, &near_point)),
+                (backward, -dot_product(&backward, &far_point)),
+            ],
+        }
+    }
+
+    // conservative, boxes near the corners can pass without actually being in view
+    pub fn intersects_box(&self, min: &Vector4<f32>, max: &Vector4<f32>) -> bool {
+        self.planes.iter().all(|(normal, distance)| {
+            // the corner furthest along the normal
+            let corner = Vector4::new(
+                if normal.x >= 0. { max.x } else { min.x },
+                if normal.y >= 0. { max.y } else { min.y },
+                if normal.z >= 0. { max.z } else { min.z },
+                1.,
+            );
+            dot_product(normal, &corner) + distance >= 0.
+        })
+    }
+}
@@ -1,15 +1,43 @@
 use nalgebra::Vector4;
+use raqote::SolidSource;
 
-use crate::drawing::colors::get_color;
+use crate::drawing::colors::{get_color, shade_color};
 use crate::linear_algebra::triangles::Triangle;
 use crate::linear_algebra::vectors::{dot_product, normalize_vec};
 
-pub fn add_lightsource(normal: Vector4<f32>, triangle: &mut Triangle) {
-    let mut light_dir: Vector4<f32> = Vector4::new(0., 1., -1., 1.);
+// world space direction toward the light
+pub fn light_dir() -> Vector4<f32> {
+    normalize_vec(&Vector4::new(0., 1., -1., 1.))
+}
 
-    light_dir = normalize_vec(&light_dir);
+// `normal` is the face's. corner normals, when the triangle has them, light each
+// corner on its own into vertex_colors so the face shades smoothly
+pub fn add_lightsource(normal: Vector4<f32>, triangle: &mut Triangle) {
+    let light_dot = dot_product(&normal, &light_dir());
 
-    let light_dot = dot_product(&normal, &light_dir);
+    triangle.color = light_color(triangle.base_color, light_dot);
+    match triangle.normals {
+        Some(normals) => {
+            let bases = match triangle.vertex_colors {
+                Some(colors) => colors.map(Some),
+                None => [triangle.base_color; 3],
+            };
+            triangle.vertex_colors = Some(
+                [0, 1, 2].map(|i| light_color(bases[i], dot_product(&normals[i], &light_dir()))),
+            );
+        }
+        None => {
+            if let Some(colors) = triangle.vertex_colors.as_mut() {
+                *colors = colors.map(|color| shade_color(color, light_dot));
+            }
+        }
+    }
+}
 
-    triangle.color = get_color(light_dot);
+// shared with the ray tracer so both renderers light surfaces the same way
+pub fn light_color(base_color: Option<SolidSource>, light_dot: f32) -> SolidSource {
+    match base_color {
+        Some(base) => shade_color(base, light_dot),
+        None => get_color(light_dot),
+    }
 }
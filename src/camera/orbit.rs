@@ -0,0 +1,83 @@
+use nalgebra::base::Vector4;
+
+use crate::camera::view::{Camera, MAX_PITCH};
+use crate::linear_algebra::vectors::{add_vec, mult_vec, sub_vec, vec_magnitude};
+
+// closest the camera may get to its focus point
+const MIN_DISTANCE: f32 = 0.1;
+
+// turntable style camera that always looks at `target` from `distance` away
+#[derive(Debug, Clone)]
+pub struct OrbitCamera {
+    pub target: Vector4<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        // matches the default camera looking at a mesh placed at z = 8
+        OrbitCamera {
+            target: Vector4::new(0., 0., 8., 1.),
+            distance: 8.,
+            yaw: 0.,
+            pitch: 0.,
+        }
+    }
+}
+
+impl OrbitCamera {
+    // picks up wherever the free camera was looking
+    pub fn from_camera(camera: &Camera, distance: f32) -> Self {
+        OrbitCamera {
+            target: add_vec(&camera.position, &mult_vec(&camera.look_dir(), distance)),
+            distance,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+        }
+    }
+
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // positive steps move closer
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance = (self.distance * 0.9_f32.powf(steps)).max(MIN_DISTANCE);
+    }
+
+    // moves the focus point in the camera's screen plane, scaled so the
+    // mesh follows the mouse at any distance
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let camera = self.camera();
+        let scale = self.distance * 0.003;
+        let offset = add_vec(
+            &mult_vec(&camera.right(), right * scale),
+            &mult_vec(&camera.up(), up * scale),
+        );
+
+        self.target = add_vec(&self.target, &offset);
+    }
+
+    // centers on a bounding box and backs off until the whole box fits in the 90 degree fov
+    pub fn frame(&mut self, min: &Vector4<f32>, max: &Vector4<f32>) {
+        let center = mult_vec(&add_vec(min, max), 0.5);
+        let radius = vec_magnitude(&sub_vec(max, min)) * 0.5;
+
+        self.target = center;
+        self.distance = (radius / 45_f32.to_radians().sin()).max(MIN_DISTANCE);
+    }
+
+    pub fn camera(&self) -> Camera {
+        let mut camera = Camera {
+            yaw: self.yaw,
+            pitch: self.pitch,
+            ..Default::default()
+        };
+        camera.position = sub_vec(&self.target, &mult_vec(&camera.look_dir(), self.distance));
+
+        camera
+    }
+}
@@ -0,0 +1,84 @@
+use nalgebra::base::{Matrix4, Vector4};
+
+use crate::linear_algebra::matrices::{create_point_at_matrix, invert_matrix};
+use crate::linear_algebra::vectors::{add_vec, cross_product, mult_vec, normalize_vec};
+
+// looking straight up or down flips the view, so stop just short of it
+pub const MAX_PITCH: f32 = 89.;
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Vector4<f32>,
+    // degrees, positive turns right
+    pub yaw: f32,
+    // degrees, positive looks up
+    pub pitch: f32,
+    // degrees, positive tilts clockwise
+    pub roll: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vector4::new(0., 0., 0., 1.),
+            yaw: 0.,
+            pitch: 0.,
+            roll: 0.,
+        }
+    }
+}
+
+impl Camera {
+    pub fn reset(&mut self) {
+        *self = Camera::default();
+    }
+
+    pub fn add_pitch(&mut self, degrees: f32) {
+        self.pitch = (self.pitch + degrees).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn look_dir(&self) -> Vector4<f32> {
+        let yaw = self.yaw.to_radians();
+        let pitch = self.pitch.to_radians();
+
+        Vector4::new(
+            -yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            yaw.cos() * pitch.cos(),
+            1.,
+        )
+    }
+
+    // right as seen on screen
+    pub fn right(&self) -> Vector4<f32> {
+        let (right, up) = self.unrolled_axes();
+        let roll = self.roll.to_radians();
+
+        add_vec(&mult_vec(&right, roll.cos()), &mult_vec(&up, -roll.sin()))
+    }
+
+    pub fn up(&self) -> Vector4<f32> {
+        let (right, up) = self.unrolled_axes();
+        let roll = self.roll.to_radians();
+
+        add_vec(&mult_vec(&up, roll.cos()), &mult_vec(&right, roll.sin()))
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let target: Vector4<f32> = add_vec(&self.position, &self.look_dir());
+        let point: Matrix4<f32> = create_point_at_matrix(self.position, target, self.up());
+
+        invert_matrix(&point)
+    }
+
+    fn unrolled_axes(&self) -> (Vector4<f32>, Vector4<f32>) {
+        let world_up: Vector4<f32> = Vector4::new(0., 1., 0., 1.);
+        let forward = self.look_dir();
+
+        // projection mirrors x, so forward x up is what ends up on the right of the screen
+        let right = normalize_vec(&cross_product(&forward, &world_up));
+        let up = normalize_vec(&cross_product(&right, &forward));
+
+        (right, up)
+    }
+}
@@ -0,0 +1,150 @@
+use nalgebra::base::Vector4;
+
+use crate::camera::view::Camera;
+use crate::linear_algebra::bvh::{Aabb, Bvh};
+use crate::linear_algebra::triangles::closest_point_on_triangle;
+use crate::linear_algebra::vectors::{add_vec, mult_vec, sub_vec, vec_magnitude};
+
+// how far below where walking started counts as having fallen off the world
+const FALL_LIMIT: f32 = 100.;
+// push out passes per step, corners can need more than one
+const RESOLVE_PASSES: usize = 4;
+
+// first person walking, the camera rides on top of a sphere that collides with the scene
+#[derive(Debug, Clone)]
+pub struct Walker {
+    pub radius: f32,
+    // eye above the bottom of the sphere
+    pub eye_height: f32,
+    // world units per second
+    pub speed: f32,
+    pub gravity: f32,
+    pub jump_speed: f32,
+    // steepest slope in degrees that can be stood on, anything steeper is a wall
+    pub max_slope: f32,
+    pub grounded: bool,
+    // vertical speed, positive is up
+    velocity: f32,
+    // where walking started, for when the camera falls off the mesh
+    spawn: Vector4<f32>,
+}
+
+impl Default for Walker {
+    fn default() -> Self {
+        Walker {
+            radius: 0.4,
+            eye_height: 1.6,
+            speed: 4.,
+            gravity: 20.,
+            jump_speed: 7.,
+            max_slope: 50.,
+            grounded: false,
+            velocity: 0.,
+            spawn: Vector4::new(0., 0., 0., 1.),
+        }
+    }
+}
+
+impl Walker {
+    // drops the camera from wherever it is, level and unrolled
+    pub fn start(&mut self, camera: &mut Camera) {
+        camera.roll = 0.;
+        self.spawn = camera.position;
+        self.velocity = 0.;
+        self.grounded = false;
+    }
+
+    // `walk` is this frame's horizontal movement, collision can shorten or deflect it
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        walk: &Vector4<f32>,
+        jump: bool,
+        bvh: &Bvh,
+        delta: f32,
+    ) {
+        let was_grounded = self.grounded && !jump;
+        if jump && self.grounded {
+            self.velocity = self.jump_speed;
+        }
+        self.velocity -= self.gravity * delta;
+
+        let eye_offset = Vector4::new(0., self.eye_height - self.radius, 0., 1.);
+        let mut center = sub_vec(&camera.position, &eye_offset);
+        let motion = add_vec(walk, &Vector4::new(0., self.velocity * delta, 0., 1.));
+
+        // steps no longer than half the radius, so falling fast can't skip through the ground
+        let steps = (vec_magnitude(&motion) / (self.radius * 0.5))
+            .ceil()
+            .max(1.);
+        let step = mult_vec(&motion, 1. / steps);
+
+        self.grounded = false;
+        for _ in 0..steps as usize {
+            center = add_vec(&center, &step);
+            center = self.resolve(center, bvh);
+        }
+
+        // walking downhill, stick to the ground rather than skipping off every bump
+        if was_grounded && !self.grounded && self.velocity <= 0. {
+            let snap = Vector4::new(0., self.radius * 0.5, 0., 1.);
+            let snapped = self.resolve(sub_vec(&center, &snap), bvh);
+            if self.grounded {
+                center = snapped;
+            }
+        }
+
+        if center.y < self.spawn.y - FALL_LIMIT {
+            center = sub_vec(&self.spawn, &eye_offset);
+            self.velocity = 0.;
+        }
+
+        camera.position = add_vec(&center, &eye_offset);
+    }
+
+    // pushes the sphere out of every triangle it overlaps
+    fn resolve(&mut self, mut center: Vector4<f32>, bvh: &Bvh) -> Vector4<f32> {
+        let min_ground_y = self.max_slope.to_radians().cos();
+
+        for _ in 0..RESOLVE_PASSES {
+            let reach = Vector4::new(self.radius, self.radius, self.radius, 1.);
+            let bounds = Aabb {
+                min: sub_vec(&center, &reach),
+                max: add_vec(&center, &reach),
+            };
+
+            let mut pushed = false;
+            for triangle in bvh.query_aabb(&bounds) {
+                let closest = closest_point_on_triangle(&center, bvh.triangle(triangle));
+                let offset = sub_vec(&center, &closest);
+                let distance = vec_magnitude(&offset);
+                if distance >= self.radius || distance <= f32::EPSILON {
+                    continue;
+                }
+
+                let normal = mult_vec(&offset, 1. / distance);
+                let depth = self.radius - distance;
+                if normal.y >= min_ground_y {
+                    // straight up rather than along the normal, so standing on a slope doesn't slide
+                    center.y += depth / normal.y;
+                    self.grounded = true;
+                    self.velocity = self.velocity.max(0.);
+                } else {
+                    // walls slide, whatever's left of the motion runs along them
+                    center = add_vec(&center, &mult_vec(&normal, depth));
+                    // a ceiling ends the jump
+                    if normal.y < -0.5 {
+                        self.velocity = self.velocity.min(0.);
+                    }
+                }
+                pushed = true;
+            }
+
+            if !pushed {
+                break;
+            }
+        }
+
+        center
+    }
+}
@@ -1,4 +1,13 @@
+pub(crate) mod background;
+pub(crate) mod capture;
 pub(crate) mod colors;
 pub(crate) mod controls;
+pub(crate) mod fog;
+pub(crate) mod overlay;
+pub(crate) mod raster;
 pub(crate) mod render;
+pub(crate) mod settings;
 pub(crate) mod shapes;
+pub(crate) mod stats;
+pub(crate) mod text;
+pub(crate) mod tracer;
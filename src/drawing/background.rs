@@ -0,0 +1,206 @@
+use image::RgbImage;
+use nalgebra::base::{Matrix4, Vector4};
+use raqote::{DrawTarget, SolidSource};
+
+use crate::camera::view::Camera;
+use crate::drawing::colors::{clear_color, mix_colors};
+use crate::linear_algebra::vectors::{
+    add_vec, dot_product, mult_vec, normalize_vec, unscale_point,
+};
+use crate::meshes::load_error::LoadError;
+use crate::{HEIGHT, WIDTH};
+
+// what's drawn behind the mesh every frame
+pub enum Background {
+    Solid(SolidSource),
+    Gradient(Gradient),
+    Skybox(Skybox),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(clear_color())
+    }
+}
+
+impl Background {
+    // color seen looking along a world space direction
+    pub fn sample(&self, dir: &Vector4<f32>) -> SolidSource {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient(gradient) => gradient.sample(dir),
+            Background::Skybox(skybox) => skybox.sample(dir),
+        }
+    }
+
+    // color all the way round at eye level, what far off things should fade into
+    pub fn horizon(&self) -> SolidSource {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient(gradient) => gradient.horizon,
+            Background::Skybox(skybox) => skybox.horizon(),
+        }
+    }
+}
+
+// sky fading into the horizon, with the ground below it, follows pitch and roll
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    pub zenith: SolidSource,
+    pub horizon: SolidSource,
+    pub ground: SolidSource,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient {
+            zenith: SolidSource {
+                r: 0x1c,
+                g: 0x24,
+                b: 0x4a,
+                a: 0xff,
+            },
+            horizon: SolidSource {
+                r: 0x8c,
+                g: 0x9c,
+                b: 0xb8,
+                a: 0xff,
+            },
+            ground: SolidSource {
+                r: 0x29,
+                g: 0x26,
+                b: 0x24,
+                a: 0xff,
+            },
+        }
+    }
+}
+
+impl Gradient {
+    fn sample(&self, dir: &Vector4<f32>) -> SolidSource {
+        match dir.y >= 0. {
+            true => mix_colors(self.horizon, self.zenith, dir.y.sqrt()),
+            // the ground darkens quickly so the horizon line reads clearly
+            false => mix_colors(self.horizon, self.ground, (-dir.y * 8.).min(1.)),
+        }
+    }
+}
+
+// cube map, faces in +x, -x, +y, -y, +z, -z order
+pub struct Skybox {
+    faces: [RgbImage; 6],
+}
+
+// per face: the axis it sits on, then the world directions of the image's right and down
+const FACES: [(Vector4<f32>, Vector4<f32>, Vector4<f32>); 6] = [
+    (unit(1., 0., 0.), unit(0., 0., 1.), unit(0., -1., 0.)),
+    (unit(-1., 0., 0.), unit(0., 0., -1.), unit(0., -1., 0.)),
+    (unit(0., 1., 0.), unit(-1., 0., 0.), unit(0., 0., 1.)),
+    (unit(0., -1., 0.), unit(-1., 0., 0.), unit(0., 0., -1.)),
+    (unit(0., 0., 1.), unit(-1., 0., 0.), unit(0., -1., 0.)),
+    (unit(0., 0., -1.), unit(1., 0., 0.), unit(0., -1., 0.)),
+];
+
+const fn unit(x: f32, y: f32, z: f32) -> Vector4<f32> {
+    Vector4::new(x, y, z, 1.)
+}
+
+impl Skybox {
+    // each image is seen from inside the cube, upright when looking at it with +y up
+    pub fn load(paths: [&str; 6]) -> Result<Skybox, LoadError> {
+        let mut faces: Vec<RgbImage> = Vec::new();
+        for path in paths.iter() {
+            faces.push(image::open(path)?.to_rgb8());
+        }
+
+        Ok(Skybox {
+            faces: faces.try_into().unwrap(),
+        })
+    }
+
+    // px.png, nx.png, py.png, ny.png, pz.png and nz.png out of one directory
+    pub fn load_dir(directory: &str) -> Result<Skybox, LoadError> {
+        let paths = ["px", "nx", "py", "ny", "pz", "nz"]
+            .map(|face| format!("{}/{}.png", directory.trim_end_matches('/'), face));
+
+        Skybox::load(paths.each_ref().map(String::as_str))
+    }
+
+    // average of the four sides, looking straight out of them and halfway between
+    fn horizon(&self) -> SolidSource {
+        let samples: Vec<SolidSource> = (0..8)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+                self.sample(&normalize_vec(&unit(angle.cos(), 0., angle.sin())))
+            })
+            .collect();
+        let average = |channel: fn(&SolidSource) -> u8| {
+            (samples.iter().map(|c| channel(c) as u32).sum::<u32>() / samples.len() as u32) as u8
+        };
+
+        SolidSource {
+            r: average(|c| c.r),
+            g: average(|c| c.g),
+            b: average(|c| c.b),
+            a: 0xff,
+        }
+    }
+
+    fn sample(&self, dir: &Vector4<f32>) -> SolidSource {
+        // the face whose axis is closest to the direction
+        let (index, (axis, right, down)) = FACES
+            .iter()
+            .enumerate()
+            .max_by(|a, b| {
+                dot_product(dir, &a.1 .0)
+                    .partial_cmp(&dot_product(dir, &b.1 .0))
+                    .unwrap()
+            })
+            .unwrap();
+
+        // project onto the face, -1..1 on both axes
+        let major = dot_product(dir, axis);
+        let u = (dot_product(dir, right) / major + 1.) * 0.5;
+        let v = (dot_product(dir, down) / major + 1.) * 0.5;
+
+        let face = &self.faces[index];
+        let x = ((u * face.width() as f32) as u32).min(face.width() - 1);
+        let y = ((v * face.height() as f32) as u32).min(face.height() - 1);
+        let [r, g, b] = face.get_pixel(x, y).0;
+
+        SolidSource { r, g, b, a: 0xff }
+    }
+}
+
+// fills dt with the background as seen by the camera, using the same projection
+// as get_triangle_queue so it turns with the mesh
+pub fn draw_background(
+    dt: &mut DrawTarget,
+    background: &Background,
+    camera: &Camera,
+    projection_matrix: &Matrix4<f32>,
+) {
+    if let Background::Solid(color) = background {
+        dt.clear(*color);
+        return;
+    }
+
+    let (width, height) = (WIDTH, HEIGHT);
+    let (forward, right, up) = (camera.look_dir(), camera.right(), camera.up());
+    let out = dt.get_data_mut();
+
+    for y in 0..height {
+        for x in 0..width {
+            // undo scale_point and the projection for the middle of the pixel
+            let (ndc_x, ndc_y) = unscale_point(x as f32 + 0.5, y as f32 + 0.5);
+            let up_offset = mult_vec(&up, ndc_y / projection_matrix[(1, 1)]);
+            // ndc x grows toward the left of the screen
+            let right_offset = mult_vec(&right, -ndc_x / projection_matrix[(0, 0)]);
+            let dir = normalize_vec(&add_vec(&add_vec(&forward, &right_offset), &up_offset));
+
+            let color = background.sample(&dir);
+            out[y * width + x] =
+                0xff00_0000 | (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32;
+        }
+    }
+}
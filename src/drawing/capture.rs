@@ -0,0 +1,212 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{imageops, Delay, Frame, ImageResult, RgbaImage};
+use nalgebra::base::Matrix4;
+use raqote::DrawTarget;
+
+use crate::linear_algebra::data::Mesh;
+use crate::meshes::export::save_mesh;
+use crate::timing::clock::Clock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    // numbered pngs in their own folder, for stitching into a video
+    Frames,
+    Gif,
+}
+
+struct Session {
+    format: RecordFormat,
+    path: PathBuf,
+    frames: u32,
+    gif: Option<GifEncoder<BufWriter<File>>>,
+}
+
+pub struct Recorder {
+    // seconds of animation per recorded frame
+    pub step: f32,
+    // where screenshots and recordings are written
+    pub directory: PathBuf,
+    session: Option<Session>,
+    // what the clock was doing before recording pinned it to `step`
+    previous_step: Option<f32>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Recorder {
+            step: 1. / 30.,
+            directory: PathBuf::from("captures"),
+            session: None,
+            previous_step: None,
+        }
+    }
+}
+
+impl Recorder {
+    pub fn is_recording(&self) -> bool {
+        self.session.is_some()
+    }
+
+    // switches the clock to a fixed step so every recorded frame advances
+    // animations by the same amount, no matter how long it took to draw
+    pub fn start(&mut self, format: RecordFormat, clock: &mut Clock) -> ImageResult<PathBuf> {
+        fs::create_dir_all(&self.directory)?;
+        let name = format!("recording-{}", timestamp());
+
+        let (path, gif) = match format {
+            RecordFormat::Frames => {
+                let path = self.directory.join(name);
+                fs::create_dir_all(&path)?;
+                (path, None)
+            }
+            RecordFormat::Gif => {
+                let path = self.directory.join(format!("{}.gif", name));
+                let mut encoder = GifEncoder::new(BufWriter::new(File::create(&path)?));
+                encoder.set_repeat(Repeat::Infinite)?;
+                (path, Some(encoder))
+            }
+        };
+
+        self.previous_step = clock.fixed_step;
+        clock.fixed_step = Some(self.step);
+        self.session = Some(Session {
+            format,
+            path: path.clone(),
+            frames: 0,
+            gif,
+        });
+
+        Ok(path)
+    }
+
+    // hands the clock back and returns where the recording went and how many frames it has
+    pub fn stop(&mut self, clock: &mut Clock) -> Option<(PathBuf, u32)> {
+        let session = self.session.take()?;
+        clock.fixed_step = self.previous_step;
+
+        // the gif trailer is written when the encoder drops
+        Some((session.path, session.frames))
+    }
+
+    pub fn capture(&mut self, dt: &DrawTarget) -> ImageResult<()> {
+        let step = self.step;
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+
+        let image = frame_to_image(dt);
+        match (session.format, session.gif.as_mut()) {
+            (RecordFormat::Gif, Some(encoder)) => {
+                let delay = Delay::from_numer_denom_ms((step * 1000.) as u32, 1);
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+            }
+            _ => {
+                image.save(
+                    session
+                        .path
+                        .join(format!("frame_{:05}.png", session.frames)),
+                )?;
+            }
+        }
+        session.frames += 1;
+
+        Ok(())
+    }
+}
+
+// saves what's on screen as a png named after the current time
+pub fn save_screenshot(dt: &DrawTarget, directory: &Path) -> ImageResult<PathBuf> {
+    fs::create_dir_all(directory)?;
+    let path = directory.join(format!("screenshot-{}.png", timestamp()));
+    frame_to_image(dt).save(&path)?;
+
+    Ok(path)
+}
+
+// writes the mesh as it's placed in the world, in whichever format save_mesh
+// picks for `extension`. an obj gets its mtl beside it
+pub fn save_mesh_snapshot(
+    mesh: &Mesh,
+    world_matrix: &Matrix4<f32>,
+    directory: &Path,
+    extension: &str,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(directory)?;
+    let path = directory.join(format!("mesh-{}.{}", timestamp(), extension));
+    save_mesh(mesh, Some(world_matrix), &path.to_string_lossy())?;
+
+    Ok(path)
+}
+
+// saves a ray traced image, and the same image beside the rasterized frame it was traced from
+pub fn save_comparison(
+    dt: &DrawTarget,
+    traced: &RgbaImage,
+    directory: &Path,
+) -> ImageResult<PathBuf> {
+    fs::create_dir_all(directory)?;
+    let stamp = timestamp();
+    traced.save(directory.join(format!("raytrace-{}.png", stamp)))?;
+
+    let raster = frame_to_image(dt);
+    let mut side_by_side = RgbaImage::new(
+        raster.width() + traced.width(),
+        raster.height().max(traced.height()),
+    );
+    imageops::replace(&mut side_by_side, &raster, 0, 0);
+    imageops::replace(&mut side_by_side, traced, raster.width() as i64, 0);
+
+    let path = directory.join(format!("compare-{}.png", stamp));
+    side_by_side.save(&path)?;
+
+    Ok(path)
+}
+
+// the window ignores alpha, so the image does too
+fn frame_to_image(dt: &DrawTarget) -> RgbaImage {
+    let (width, height) = (dt.width() as u32, dt.height() as u32);
+    let mut image = RgbaImage::new(width, height);
+
+    for (pixel, argb) in image.pixels_mut().zip(dt.get_data().iter()) {
+        pixel.0 = [(argb >> 16) as u8, (argb >> 8) as u8, *argb as u8, 0xff];
+    }
+
+    image
+}
+
+// utc, 2023-04-01_13-45-09-250
+fn timestamp() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (days, time) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // days since 1970-01-01 -> civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}-{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    )
+}
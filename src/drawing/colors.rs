@@ -1,5 +1,10 @@
 use raqote::SolidSource;
 
+// background the screen is cleared to every frame
+pub fn clear_color() -> SolidSource {
+    SolidSource::from_unpremultiplied_argb(0x29, 0x2c, 0x3c, 0xff)
+}
+
 pub fn get_color(dot: f32) -> SolidSource {
     let scaled = dot * 10.;
     let color = match scaled {
@@ -74,3 +79,42 @@ pub fn get_color(dot: f32) -> SolidSource {
 
     color
 }
+
+// scales a base color by the light, keeping a bit of ambient so unlit faces aren't black
+pub fn shade_color(base: SolidSource, dot: f32) -> SolidSource {
+    let intensity = 0.2 + 0.8 * dot.clamp(0., 1.);
+
+    SolidSource {
+        r: (base.r as f32 * intensity) as u8,
+        g: (base.g as f32 * intensity) as u8,
+        b: (base.b as f32 * intensity) as u8,
+        a: base.a,
+    }
+}
+
+// linear blend between two colors, t in 0..1
+pub fn mix_colors(a: SolidSource, b: SolidSource, t: f32) -> SolidSource {
+    let t = t.clamp(0., 1.);
+    let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t) as u8;
+
+    SolidSource {
+        r: mix(a.r, b.r),
+        g: mix(a.g, b.g),
+        b: mix(a.b, b.b),
+        a: mix(a.a, b.a),
+    }
+}
+
+// flat stand in for per corner colors, used where they can't be blended
+pub fn average_color(colors: &[SolidSource; 3]) -> SolidSource {
+    let average = |channel: fn(&SolidSource) -> u8| {
+        (colors.iter().map(|c| channel(c) as u32).sum::<u32>() / 3) as u8
+    };
+
+    SolidSource {
+        r: average(|c| c.r),
+        g: average(|c| c.g),
+        b: average(|c| c.b),
+        a: average(|c| c.a),
+    }
+}
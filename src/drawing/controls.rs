@@ -1,59 +1,362 @@
-use crate::linear_algebra::vectors::{add_vec, mult_vec, sub_vec};
-use minifb::{Key, Window};
-use nalgebra::base::Vector4;
-use raqote::{DrawTarget, SolidSource};
+use crate::camera::orbit::OrbitCamera;
+use crate::camera::view::Camera;
+use crate::camera::walk::Walker;
+use crate::drawing::capture::{save_screenshot, RecordFormat, Recorder};
+use crate::input::actions::Action;
+use crate::input::bindings::Bindings;
+use crate::linear_algebra::bvh::Bvh;
+use crate::linear_algebra::ray::{Pick, Ray};
+use crate::linear_algebra::vectors::{add_vec, mult_vec, normalize_vec, sub_vec, vec_magnitude};
+use crate::timing::clock::Clock;
+use crate::{HEIGHT, WIDTH};
+use minifb::{MouseButton, MouseMode, Window};
+use nalgebra::base::{Matrix4, Vector4};
+use raqote::DrawTarget;
+
+// world units per second
+const STRAFE_SPEED: f32 = 12.;
+// degrees per second
+const TURN_SPEED: f32 = 60.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    // free flying first person camera
+    Fly,
+    // circles a focus point, driven by the mouse
+    Orbit,
+    // on foot, colliding with the mesh, see walk_controls
+    Walk,
+}
+
+pub struct MouseLook {
+    // while captured, moving the mouse turns the camera and the cursor is hidden
+    pub captured: bool,
+    // degrees per pixel of mouse movement
+    pub sensitivity: f32,
+    last_pos: Option<(f32, f32)>,
+}
+
+impl Default for MouseLook {
+    fn default() -> Self {
+        MouseLook {
+            captured: false,
+            sensitivity: 0.3,
+            last_pos: None,
+        }
+    }
+}
+
+// the camera and everything steering it, whichever mode is active
+pub struct CameraControls {
+    pub camera: Camera,
+    // kept while flying so switching back resumes where it was
+    pub orbit: OrbitCamera,
+    pub mode: CameraMode,
+    pub mouse: MouseLook,
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        CameraControls {
+            camera: Camera::default(),
+            orbit: OrbitCamera::default(),
+            mode: CameraMode::Fly,
+            mouse: MouseLook::default(),
+        }
+    }
+}
 
 pub fn initialize_user_controls(
-    window: &Window,
-    dt: &mut DrawTarget,
-    camera: &mut Vector4<f32>,
-    look_dir: &mut Vector4<f32>,
-    yaw: &mut f32,
+    window: &mut Window,
+    controls: &mut CameraControls,
+    bindings: &Bindings,
     speed: &f32,
+    delta: &f32,
 ) {
-    // clear screen
-    dt.clear(SolidSource::from_unpremultiplied_argb(
-        0x29, 0x2c, 0x3c, 0xff,
-    ));
+    let CameraControls {
+        camera,
+        orbit,
+        mode,
+        mouse,
+    } = controls;
+
+    // switching camera modes
+    if bindings.is_pressed(window, Action::ToggleCameraMode) {
+        *mode = match mode {
+            CameraMode::Fly | CameraMode::Walk => {
+                *orbit = OrbitCamera::from_camera(camera, orbit.distance);
+                CameraMode::Orbit
+            }
+            CameraMode::Orbit => CameraMode::Fly,
+        };
+    }
+
+    match mode {
+        CameraMode::Orbit => {
+            orbit_controls(window, camera, orbit, mouse, bindings);
+            return;
+        }
+        // needs the scene to collide with, so main drives it through walk_controls
+        CameraMode::Walk => return,
+        CameraMode::Fly => {}
+    }
 
     // user input
-    // back and forth
-    if window.is_key_down(Key::Up) {
-        camera.y += 0.2;
+    // strafing + up and down
+    let strafe_vel: Vector4<f32> = mult_vec(&camera.right(), STRAFE_SPEED * delta);
+    let vertical_vel: Vector4<f32> = Vector4::new(0., STRAFE_SPEED * delta, 0., 1.);
+    if bindings.is_down(window, Action::MoveUp) {
+        camera.position = add_vec(&camera.position, &vertical_vel);
     }
-    if window.is_key_down(Key::Left) {
-        camera.x += 0.2;
+    if bindings.is_down(window, Action::StrafeLeft) {
+        camera.position = sub_vec(&camera.position, &strafe_vel);
     }
-    if window.is_key_down(Key::Down) {
-        camera.y -= 0.2;
+    if bindings.is_down(window, Action::MoveDown) {
+        camera.position = sub_vec(&camera.position, &vertical_vel);
     }
-    if window.is_key_down(Key::Right) {
-        camera.x -= 0.2;
+    if bindings.is_down(window, Action::StrafeRight) {
+        camera.position = add_vec(&camera.position, &strafe_vel);
     }
     // reset
-    if window.is_key_down(Key::Tab) {
-        *camera = Vector4::new(0., 0., 0., 1.);
-        *look_dir = Vector4::new(0., 0., 1., 1.);
-        *yaw = 0.;
+    if bindings.is_down(window, Action::Reset) {
+        camera.reset();
     }
 
-    // turning camera
-    // up + down
-    let forward_vel: Vector4<f32> = mult_vec(&look_dir, *speed);
-    if window.is_key_down(Key::W) {
-        *camera = add_vec(&camera, &forward_vel);
+    // moving along where the camera looks
+    let forward_vel: Vector4<f32> = mult_vec(&camera.look_dir(), speed * delta);
+    if bindings.is_down(window, Action::MoveForward) {
+        camera.position = add_vec(&camera.position, &forward_vel);
     }
 
-    if window.is_key_down(Key::S) {
-        *camera = sub_vec(&camera, &forward_vel);
+    if bindings.is_down(window, Action::MoveBack) {
+        camera.position = sub_vec(&camera.position, &forward_vel);
     }
 
+    // turning camera
     // l + r
-    if window.is_key_down(Key::A) {
-        *yaw -= 1.;
+    if bindings.is_down(window, Action::YawLeft) {
+        camera.yaw -= TURN_SPEED * delta;
     }
 
-    if window.is_key_down(Key::D) {
-        *yaw += 1.;
+    if bindings.is_down(window, Action::YawRight) {
+        camera.yaw += TURN_SPEED * delta;
+    }
+
+    // roll
+    if bindings.is_down(window, Action::RollLeft) {
+        camera.roll -= TURN_SPEED * delta;
+    }
+
+    if bindings.is_down(window, Action::RollRight) {
+        camera.roll += TURN_SPEED * delta;
+    }
+
+    mouse_look(window, camera, mouse, bindings);
+}
+
+// walking on the mesh: movement stays level, turning and mouse look work like flying
+pub fn walk_controls(
+    window: &mut Window,
+    camera: &mut Camera,
+    walker: &mut Walker,
+    mouse: &mut MouseLook,
+    bindings: &Bindings,
+    bvh: &Bvh,
+    delta: &f32,
+) {
+    let level = |v: Vector4<f32>| match v.x == 0. && v.z == 0. {
+        true => v,
+        false => normalize_vec(&Vector4::new(v.x, 0., v.z, 1.)),
+    };
+    let forward = level(camera.look_dir());
+    let right = level(camera.right());
+
+    let mut walk: Vector4<f32> = Vector4::new(0., 0., 0., 1.);
+    for (action, direction) in [
+        (Action::MoveForward, forward),
+        (Action::MoveBack, mult_vec(&forward, -1.)),
+        (Action::StrafeRight, right),
+        (Action::StrafeLeft, mult_vec(&right, -1.)),
+    ] {
+        if bindings.is_down(window, action) {
+            walk = add_vec(&walk, &direction);
+        }
+    }
+    if vec_magnitude(&walk) > 0. {
+        walk = mult_vec(&normalize_vec(&walk), walker.speed * delta);
+    }
+
+    let jump = bindings.is_down(window, Action::Jump);
+    walker.update(camera, &walk, jump, bvh, *delta);
+
+    if bindings.is_down(window, Action::YawLeft) {
+        camera.yaw -= TURN_SPEED * delta;
+    }
+    if bindings.is_down(window, Action::YawRight) {
+        camera.yaw += TURN_SPEED * delta;
+    }
+
+    mouse_look(window, camera, mouse, bindings);
+}
+
+// pausing, stepping single frames and slow motion
+pub fn initialize_clock_controls(window: &Window, clock: &mut Clock, bindings: &Bindings) {
+    if bindings.is_pressed(window, Action::Pause) {
+        clock.toggle_pause();
+    }
+    if bindings.is_repeated(window, Action::StepFrame) {
+        clock.step();
+    }
+    if bindings.is_pressed(window, Action::SlowDown) {
+        clock.slower();
     }
+    if bindings.is_pressed(window, Action::SpeedUp) {
+        clock.faster();
+    }
+}
+
+// screenshots and recording, call once the frame is finished so overlays are included
+pub fn initialize_capture_controls(
+    window: &Window,
+    dt: &DrawTarget,
+    recorder: &mut Recorder,
+    clock: &mut Clock,
+    bindings: &Bindings,
+) {
+    if bindings.is_pressed(window, Action::Screenshot) {
+        match save_screenshot(dt, &recorder.directory) {
+            Ok(path) => println!("saved screenshot to {}", path.display()),
+            Err(e) => eprintln!("couldn't save screenshot: {}", e),
+        }
+    }
+
+    for (action, format) in [
+        (Action::ToggleRecording, RecordFormat::Frames),
+        (Action::ToggleGifRecording, RecordFormat::Gif),
+    ] {
+        if !bindings.is_pressed(window, action) {
+            continue;
+        }
+
+        if recorder.is_recording() {
+            if let Some((path, frames)) = recorder.stop(clock) {
+                println!("saved {} frames to {}", frames, path.display());
+            }
+        } else {
+            match recorder.start(format, clock) {
+                Ok(path) => println!("recording to {}", path.display()),
+                Err(e) => eprintln!("couldn't start recording: {}", e),
+            }
+        }
+    }
+
+    if let Err(e) = recorder.capture(dt) {
+        eprintln!("recording stopped: {}", e);
+        recorder.stop(clock);
+    }
+}
+
+// casts a ray through the cursor, or the middle of the screen while the mouse is captured,
+// and reports what it hit on stdout
+pub fn pick_under_cursor(
+    window: &Window,
+    camera: &Camera,
+    mouse: &MouseLook,
+    bvh: &Bvh,
+    projection_matrix: &Matrix4<f32>,
+) -> Option<Pick> {
+    let (x, y) = match mouse.captured {
+        true => (WIDTH as f32 * 0.5, HEIGHT as f32 * 0.5),
+        false => window.get_mouse_pos(MouseMode::Discard)?,
+    };
+
+    let ray = Ray::from_screen(x, y, camera, projection_matrix);
+    let pick = bvh.intersect_ray(&ray);
+
+    match &pick {
+        Some(pick) => println!(
+            "picked triangle {} at {:.3} {:.3} {:.3}, normal {:.3} {:.3} {:.3}, {:.3} away",
+            pick.triangle,
+            pick.position.x,
+            pick.position.y,
+            pick.position.z,
+            pick.normal.x,
+            pick.normal.y,
+            pick.normal.z,
+            pick.distance
+        ),
+        None => println!("nothing under the cursor"),
+    }
+
+    pick
+}
+
+// capture_mouse toggles capture, holding the right mouse button looks around without capturing
+fn mouse_look(
+    window: &mut Window,
+    camera: &mut Camera,
+    mouse: &mut MouseLook,
+    bindings: &Bindings,
+) {
+    if bindings.is_pressed(window, Action::CaptureMouse) {
+        mouse.captured = !mouse.captured;
+        window.set_cursor_visibility(!mouse.captured);
+    }
+
+    let looking = mouse.captured || window.get_mouse_down(MouseButton::Right);
+
+    if let (true, Some((dx, dy))) = (looking, mouse_delta(window, mouse)) {
+        camera.yaw += dx * mouse.sensitivity;
+        // screen y grows downwards
+        camera.add_pitch(-dy * mouse.sensitivity);
+    }
+}
+
+// left drag orbits, orbit_pan + left drag pans, scrolling zooms
+fn orbit_controls(
+    window: &mut Window,
+    camera: &mut Camera,
+    orbit: &mut OrbitCamera,
+    mouse: &mut MouseLook,
+    bindings: &Bindings,
+) {
+    if mouse.captured {
+        mouse.captured = false;
+        window.set_cursor_visibility(true);
+    }
+
+    let delta = mouse_delta(window, mouse);
+    if let (true, Some((dx, dy))) = (window.get_mouse_down(MouseButton::Left), delta) {
+        if bindings.is_down(window, Action::OrbitPan) {
+            orbit.pan(-dx, dy);
+        } else {
+            orbit.rotate(dx * mouse.sensitivity, dy * mouse.sensitivity);
+        }
+    }
+
+    // sideways scrolling leaves the zoom alone, 0.0.signum() would be 1
+    if let Some((_, scroll)) = window.get_scroll_wheel() {
+        if scroll != 0. {
+            orbit.zoom(scroll.signum());
+        }
+    }
+
+    // reset
+    if bindings.is_down(window, Action::Reset) {
+        *orbit = OrbitCamera::default();
+    }
+
+    *camera = orbit.camera();
+}
+
+// how far the mouse moved since last frame, None until there are two positions to compare
+fn mouse_delta(window: &Window, mouse: &mut MouseLook) -> Option<(f32, f32)> {
+    let pos = window.get_unscaled_mouse_pos(MouseMode::Pass);
+    let delta = match (pos, mouse.last_pos) {
+        (Some((x, y)), Some((last_x, last_y))) => Some((x - last_x, y - last_y)),
+        _ => None,
+    };
+
+    mouse.last_pos = pos;
+    delta
 }
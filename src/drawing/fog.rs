@@ -0,0 +1,102 @@
+use raqote::SolidSource;
+
+use crate::drawing::background::Background;
+use crate::drawing::colors::{clear_color, mix_colors};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    Off,
+    // ramps from nothing at `start` to solid at `end`
+    Linear,
+    // 1 - e^(-density * depth)
+    Exponential,
+    // 1 - e^(-(density * depth)^2), clearer up close with a sharper falloff
+    ExponentialSquared,
+}
+
+impl FogMode {
+    pub const ALL: [FogMode; 4] = [
+        FogMode::Off,
+        FogMode::Linear,
+        FogMode::Exponential,
+        FogMode::ExponentialSquared,
+    ];
+
+    pub fn next(&self) -> FogMode {
+        let index = FogMode::ALL
+            .iter()
+            .position(|mode| mode == self)
+            .unwrap_or(0);
+        FogMode::ALL[(index + 1) % FogMode::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FogMode::Off => "off",
+            FogMode::Linear => "linear",
+            FogMode::Exponential => "exp",
+            FogMode::ExponentialSquared => "exp2",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FogSettings {
+    pub mode: FogMode,
+    pub color: SolidSource,
+    // only used by the exponential modes
+    pub density: f32,
+    // view space depth, only used by linear
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        FogSettings {
+            mode: FogMode::Off,
+            // the window ignores alpha, so fully fogged faces match the background when opaque
+            color: SolidSource {
+                a: 0xff,
+                ..clear_color()
+            },
+            density: 0.05,
+            start: 10.,
+            end: 50.,
+        }
+    }
+}
+
+impl FogSettings {
+    // how much of the fog color to use at this view space depth, 0..1
+    pub fn amount(&self, depth: f32) -> f32 {
+        let depth = depth.max(0.);
+        let amount = match self.mode {
+            FogMode::Off => 0.,
+            FogMode::Linear => (depth - self.start) / (self.end - self.start).max(f32::EPSILON),
+            FogMode::Exponential => 1. - (-self.density * depth).exp(),
+            FogMode::ExponentialSquared => 1. - (-(self.density * depth).powi(2)).exp(),
+        };
+
+        amount.clamp(0., 1.)
+    }
+
+    // fades into the background's horizon, so far off faces disappear into it
+    pub fn with_background(self, background: &Background) -> Self {
+        FogSettings {
+            // the window ignores alpha, so fully fogged faces match the background when opaque
+            color: SolidSource {
+                a: 0xff,
+                ..background.horizon()
+            },
+            ..self
+        }
+    }
+
+    pub fn apply(&self, color: SolidSource, depth: f32) -> SolidSource {
+        match self.mode {
+            FogMode::Off => color,
+            _ => mix_colors(color, self.color, self.amount(depth)),
+        }
+    }
+}
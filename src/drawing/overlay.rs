@@ -0,0 +1,197 @@
+use std::time::{Duration, Instant};
+
+use raqote::{DrawOptions, DrawTarget, SolidSource, Source};
+
+use crate::camera::view::Camera;
+use crate::drawing::fog::{FogMode, FogSettings};
+use crate::drawing::settings::RenderSettings;
+use crate::drawing::stats::FrameStats;
+use crate::drawing::text::{draw_text, text_width, CHAR_ADVANCE, LINE_HEIGHT};
+use crate::input::actions::Action;
+use crate::input::bindings::Bindings;
+use crate::linear_algebra::ray::Pick;
+use crate::{HEIGHT, WIDTH};
+
+const TEXT_COLOR: SolidSource = SolidSource {
+    r: 0xf4,
+    g: 0xdc,
+    b: 0xd7,
+    a: 0xff,
+};
+
+const PANEL_COLOR: SolidSource = SolidSource {
+    r: 0x18,
+    g: 0x15,
+    b: 0x15,
+    a: 0xff,
+};
+
+// how long a passing notice stays up, errors stay until the next one
+const NOTICE_TIME: Duration = Duration::from_secs(3);
+
+// bindings that fit down the screen, leaving a line for the page number
+const HELP_ROWS: usize = ((HEIGHT as i32 - 8) / LINE_HEIGHT - 1) as usize;
+
+// how many pages draw_help splits the bindings over
+pub fn help_pages() -> usize {
+    Action::ALL.len().div_ceil(HELP_ROWS)
+}
+
+// one screenful of actions and what they're bound to, on a dimmed panel
+pub fn draw_help(dt: &mut DrawTarget, bindings: &Bindings, page: usize) {
+    let mut lines: Vec<String> = Action::ALL
+        .iter()
+        .skip(page * HELP_ROWS)
+        .take(HELP_ROWS)
+        .map(|action| format!("{:<18} {}", action.name(), bindings.label(*action)))
+        .collect();
+    lines.push(format!(
+        "page {}/{}, {} for the next",
+        page + 1,
+        help_pages(),
+        bindings.label(Action::ToggleHelp)
+    ));
+
+    draw_lines(dt, 4, 4, &lines);
+}
+
+// a line of news for draw_hud, the last thing that happened
+#[derive(Debug, Clone, Default)]
+pub struct Notice {
+    // text and when it was shown, errors have no time since they don't fade
+    current: Option<(String, Option<Instant>)>,
+}
+
+impl Notice {
+    // fades after NOTICE_TIME
+    pub fn show(&mut self, text: String) {
+        self.current = Some((text, Some(Instant::now())));
+    }
+
+    // stays until the next one
+    pub fn pin(&mut self, text: String) {
+        self.current = Some((text, None));
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match &self.current {
+            Some((text, None)) => Some(text),
+            Some((text, Some(shown))) if shown.elapsed() < NOTICE_TIME => Some(text),
+            _ => None,
+        }
+    }
+}
+
+// frame stats and camera state in the bottom left corner, with `notice`
+// (say a mesh that failed to reload) wrapped above them
+pub fn draw_hud(
+    dt: &mut DrawTarget,
+    stats: &FrameStats,
+    camera: &Camera,
+    settings: &RenderSettings,
+    fog: &FogSettings,
+    selection: Option<&Pick>,
+    notice: Option<&str>,
+) {
+    let mut lines: Vec<String> = notice.map(wrap).unwrap_or_default();
+    lines.extend([
+        format!("{:.1} fps  {:.2} ms", stats.fps, stats.frame_time * 1000.),
+        format!("tris {}  culled {}", stats.submitted, stats.culled),
+        format!("clipped {}  drawn {}", stats.clipped, stats.drawn),
+        format!(
+            "raster {:.2} ms  shaded {}",
+            stats.raster_time * 1000.,
+            stats.shaded
+        ),
+        format!(
+            "pos {:.2} {:.2} {:.2}",
+            camera.position.x, camera.position.y, camera.position.z
+        ),
+        format!("yaw {:.1}  pitch {:.1}", camera.yaw, camera.pitch),
+        format!(
+            "mode {}  aa {}{}",
+            settings.mode.name(),
+            settings.anti_aliasing.name(),
+            if settings.fxaa { "+fxaa" } else { "" }
+        ),
+    ]);
+    if fog.mode != FogMode::Off {
+        lines.push(format!("fog {}", fog.mode.name()));
+    }
+    if stats.lod_levels > 1 {
+        lines.push(format!(
+            "lod {}/{}  {}",
+            stats.lod,
+            stats.lod_levels - 1,
+            if stats.lod_fixed { "fixed" } else { "auto" }
+        ));
+    }
+    if let Some(pick) = selection {
+        lines.push(format!(
+            "pick tri {}  dist {:.2}",
+            pick.triangle, pick.distance
+        ));
+        lines.push(format!(
+            "at {:.2} {:.2} {:.2}",
+            pick.position.x, pick.position.y, pick.position.z
+        ));
+        lines.push(format!(
+            "normal {:.2} {:.2} {:.2}",
+            pick.normal.x, pick.normal.y, pick.normal.z
+        ));
+    }
+
+    let y = HEIGHT as i32 - 4 - lines.len() as i32 * LINE_HEIGHT;
+    draw_lines(dt, 4, y, &lines);
+}
+
+// splits text into lines that fit across the screen, between words where it can
+fn wrap(text: &str) -> Vec<String> {
+    let columns = ((WIDTH as i32 - 8) / CHAR_ADVANCE) as usize;
+    let mut lines: Vec<Vec<char>> = Vec::new();
+    let mut line: Vec<char> = Vec::new();
+
+    for word in text.split_whitespace() {
+        let word: Vec<char> = word.chars().collect();
+        if !line.is_empty() && line.len() + 1 + word.len() > columns {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+        // a word too long for a line on its own is cut up
+        while line.len() > columns {
+            let rest = line.split_off(columns);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines.into_iter().map(String::from_iter).collect()
+}
+
+// left aligned block of text with a translucent backing panel
+pub fn draw_lines(dt: &mut DrawTarget, x: i32, y: i32, lines: &[String]) {
+    let padding = 3;
+    let width = lines.iter().map(|line| text_width(line)).max().unwrap_or(0);
+    let height = lines.len() as i32 * LINE_HEIGHT;
+
+    dt.fill_rect(
+        (x - padding) as f32,
+        (y - padding) as f32,
+        (width + padding * 2) as f32,
+        (height + padding * 2) as f32,
+        &Source::Solid(PANEL_COLOR),
+        &DrawOptions {
+            alpha: 0.75,
+            ..DrawOptions::new()
+        },
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(dt, x, y + i as i32 * LINE_HEIGHT, line, TEXT_COLOR);
+    }
+}
@@ -0,0 +1,466 @@
+use raqote::{DrawTarget, SolidSource};
+
+use crate::drawing::settings::{AntiAliasing, RenderMode, RenderSettings};
+use crate::linear_algebra::triangles::Triangle;
+
+// one sample in the middle of the pixel
+const CENTER: [(f32, f32); 1] = [(0.5, 0.5)];
+
+// rotated grid, so near horizontal and near vertical edges both get four distinct steps
+const ROTATED_GRID: [(f32, f32); 4] = [
+    (0.375, 0.125),
+    (0.875, 0.375),
+    (0.125, 0.625),
+    (0.625, 0.875),
+];
+
+// software triangle rasterizer with a depth buffer, used instead of raqote's
+// path filling when anti aliasing is switched on
+pub struct Rasterizer {
+    // output size in pixels
+    width: usize,
+    height: usize,
+    // ssaa: every output pixel is scale x scale buffer pixels
+    scale: usize,
+    // sample positions inside a buffer pixel
+    samples: &'static [(f32, f32)],
+    buffer_width: usize,
+    buffer_height: usize,
+    // buffer_width * buffer_height * samples, premultiplied argb like raqote
+    color: Vec<u32>,
+    depth: Vec<f32>,
+    // color as it was before any triangle, what wireframe faces paint back
+    background: Vec<u32>,
+    // colors worked out for faces so far, what anti aliasing costs
+    shaded: usize,
+}
+
+impl Rasterizer {
+    // starts from whatever is already in dt, so the cleared background shows through
+    pub fn new(mode: AntiAliasing, dt: &DrawTarget) -> Self {
+        match mode {
+            AntiAliasing::Ssaa2x => Rasterizer::with_samples(2, &CENTER, dt),
+            AntiAliasing::Ssaa4x => Rasterizer::with_samples(4, &CENTER, dt),
+            AntiAliasing::Msaa4x => Rasterizer::with_samples(1, &ROTATED_GRID, dt),
+            AntiAliasing::Vector | AntiAliasing::Off => Rasterizer::with_samples(1, &CENTER, dt),
+        }
+    }
+
+    fn with_samples(scale: usize, samples: &'static [(f32, f32)], dt: &DrawTarget) -> Self {
+        let (width, height) = (dt.width() as usize, dt.height() as usize);
+        let (buffer_width, buffer_height) = (width * scale, height * scale);
+        let background = dt.get_data();
+
+        let mut color: Vec<u32> = Vec::with_capacity(buffer_width * buffer_height * samples.len());
+        for y in 0..buffer_height {
+            for x in 0..buffer_width {
+                let pixel = background[(y / scale) * width + x / scale];
+                color.extend(std::iter::repeat_n(pixel, samples.len()));
+            }
+        }
+
+        Rasterizer {
+            width,
+            height,
+            scale,
+            samples,
+            buffer_width,
+            buffer_height,
+            depth: vec![f32::INFINITY; color.len()],
+            background: color.clone(),
+            color,
+            shaded: 0,
+        }
+    }
+
+    pub fn shaded(&self) -> usize {
+        self.shaded
+    }
+
+    // same modes as render_triangle, lines and points aren't depth tested and
+    // rely on the painter's order of the queue like the vector path does
+    pub fn draw(&mut self, triangle: &Triangle, settings: &RenderSettings) {
+        match settings.mode {
+            RenderMode::Shaded | RenderMode::Normals => {
+                self.fill_triangle(triangle, Some(corner_colors(triangle)));
+            }
+            RenderMode::Wireframe => {
+                // putting the background back keeps hidden lines hidden
+                self.fill_triangle(triangle, None);
+                self.outline(triangle, settings);
+            }
+            RenderMode::ShadedWireframe => {
+                self.fill_triangle(triangle, Some(corner_colors(triangle)));
+                self.outline(triangle, settings);
+            }
+            RenderMode::Points => {
+                let colors = corner_colors(triangle);
+                for (vertex, color) in triangle.vertices.iter().zip(colors) {
+                    let half = settings.point_size * 0.5;
+                    self.stamp(vertex.x, vertex.y, half, false, pack(color));
+                }
+            }
+        }
+    }
+
+    // colors are blended across the face by the barycentric weights, gouraud style.
+    // without colors the face shows whatever background is behind it. each sample
+    // is depth tested on its own, but the color is worked out once per buffer pixel
+    // and shared by every sample the face wins, so msaa shades no more than off
+    fn fill_triangle(&mut self, triangle: &Triangle, colors: Option<[SolidSource; 3]>) {
+        let scale = self.scale as f32;
+        let [a, b, c] = triangle.vertices.map(|v| (v.x * scale, v.y * scale, v.z));
+
+        let area = edge(a, b, c);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let (min_x, max_x) = span(
+            a.0.min(b.0).min(c.0),
+            a.0.max(b.0).max(c.0),
+            self.buffer_width,
+        );
+        let (min_y, max_y) = span(
+            a.1.min(b.1).min(c.1),
+            a.1.max(b.1).max(c.1),
+            self.buffer_height,
+        );
+
+        let packed = colors.map(|colors| colors.map(pack));
+        let flat = packed.is_some_and(|p| p[0] == p[1] && p[1] == p[2]);
+        let count = self.samples.len();
+        let mut shaded = 0;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let mut color: Option<u32> = None;
+                for (i, (offset_x, offset_y)) in self.samples.iter().enumerate() {
+                    let p = (x as f32 + offset_x, y as f32 + offset_y, 0.);
+
+                    // barycentric weights, dividing by the area handles either winding
+                    let w0 = edge(b, c, p) / area;
+                    let w1 = edge(c, a, p) / area;
+                    let w2 = edge(a, b, p) / area;
+                    if w0 < 0. || w1 < 0. || w2 < 0. {
+                        continue;
+                    }
+
+                    let z = w0 * a.2 + w1 * b.2 + w2 * c.2;
+                    let index = (y * self.buffer_width + x) * count + i;
+                    if z < self.depth[index] {
+                        self.depth[index] = z;
+                        self.color[index] = match packed {
+                            None => self.background[index],
+                            Some(packed) => *color.get_or_insert_with(|| {
+                                shaded += 1;
+                                match flat {
+                                    true => packed[0],
+                                    false => {
+                                        blend_argb(packed, center_weights(a, b, c, area, x, y))
+                                    }
+                                }
+                            }),
+                        };
+                    }
+                }
+            }
+        }
+        self.shaded += shaded;
+    }
+
+    fn outline(&mut self, triangle: &Triangle, settings: &RenderSettings) {
+        let half = settings.line_width * 0.5;
+        let packed = pack(settings.line_color);
+
+        for i in 0..3 {
+            let from = triangle.vertices[i];
+            let to = triangle.vertices[(i + 1) % 3];
+
+            // stamp round brushes every half pixel along the edge
+            let length = ((to.x - from.x).powi(2) + (to.y - from.y).powi(2)).sqrt();
+            let steps = (length * 2. * self.scale as f32).ceil().max(1.) as usize;
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let x = from.x + (to.x - from.x) * t;
+                let y = from.y + (to.y - from.y) * t;
+                self.stamp(x, y, half, true, packed);
+            }
+        }
+    }
+
+    // colors every sample within half of (x, y), in output pixels
+    fn stamp(&mut self, x: f32, y: f32, half: f32, round: bool, packed: u32) {
+        let scale = self.scale as f32;
+        let (x, y) = (x * scale, y * scale);
+        // never thinner than a buffer pixel, or lines break up
+        let half = (half * scale).max(0.5);
+        let count = self.samples.len();
+
+        let (min_x, max_x) = span(x - half, x + half, self.buffer_width);
+        let (min_y, max_y) = span(y - half, y + half, self.buffer_height);
+
+        for py in min_y..max_y {
+            for px in min_x..max_x {
+                for (i, (offset_x, offset_y)) in self.samples.iter().enumerate() {
+                    let dx = px as f32 + offset_x - x;
+                    let dy = py as f32 + offset_y - y;
+                    let inside = match round {
+                        true => dx * dx + dy * dy <= half * half,
+                        false => dx.abs() <= half && dy.abs() <= half,
+                    };
+                    if inside {
+                        self.color[(py * self.buffer_width + px) * count + i] = packed;
+                    }
+                }
+            }
+        }
+    }
+
+    // averages every sample behind each output pixel back into dt, a box filter
+    pub fn resolve(&self, dt: &mut DrawTarget) {
+        let count = self.samples.len();
+        let per_pixel = (self.scale * self.scale * count) as u32;
+        let out = dt.get_data_mut();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut sum: [u32; 4] = [0; 4];
+                for sy in 0..self.scale {
+                    let row = (y * self.scale + sy) * self.buffer_width;
+                    let start = (row + x * self.scale) * count;
+                    for pixel in &self.color[start..start + self.scale * count] {
+                        for (channel, total) in sum.iter_mut().enumerate() {
+                            *total += pixel >> (24 - channel * 8) & 0xff;
+                        }
+                    }
+                }
+
+                out[y * self.width + x] = sum
+                    .iter()
+                    .fold(0, |argb, total| (argb << 8) | (total / per_pixel));
+            }
+        }
+    }
+}
+
+// twice the signed area of abc, or which side of ab c is on
+fn edge(a: (f32, f32, f32), b: (f32, f32, f32), c: (f32, f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// barycentric weights at the middle of pixel (x, y). a pixel the face only
+// partly covers can have its middle outside it, that's pulled back onto the face
+fn center_weights(
+    a: (f32, f32, f32),
+    b: (f32, f32, f32),
+    c: (f32, f32, f32),
+    area: f32,
+    x: usize,
+    y: usize,
+) -> [f32; 3] {
+    let p = (x as f32 + 0.5, y as f32 + 0.5, 0.);
+    let weights = [edge(b, c, p), edge(c, a, p), edge(a, b, p)].map(|w| (w / area).max(0.));
+    let sum: f32 = weights.iter().sum();
+
+    weights.map(|w| w / sum)
+}
+
+// pixel range covering min..max, clamped to the buffer
+fn span(min: f32, max: f32, size: usize) -> (usize, usize) {
+    let start = min.floor().max(0.) as usize;
+    let end = (max.ceil().max(0.) as usize + 1).min(size);
+
+    (start.min(end), end)
+}
+
+fn corner_colors(triangle: &Triangle) -> [SolidSource; 3] {
+    triangle.vertex_colors.unwrap_or([triangle.color; 3])
+}
+
+// weighted sum of three packed colors, channel by channel
+fn blend_argb(colors: [u32; 3], weights: [f32; 3]) -> u32 {
+    (0..4).fold(0, |argb, channel| {
+        let shift = 24 - channel * 8;
+        let value: f32 = colors
+            .iter()
+            .zip(weights)
+            .map(|(color, weight)| (color >> shift & 0xff) as f32 * weight)
+            .sum();
+        argb << 8 | value.round().clamp(0., 255.) as u32
+    })
+}
+
+fn pack(color: SolidSource) -> u32 {
+    (color.a as u32) << 24 | (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32
+}
+
+// contrast below this, relative to the brightest neighbour, isn't treated as an edge
+const FXAA_THRESHOLD: f32 = 0.125;
+// ignores contrast in very dark areas
+const FXAA_THRESHOLD_MIN: f32 = 0.0312;
+
+// cheap fxaa style pass: find pixels on a luma edge, work out whether the edge
+// runs horizontally or vertically and blend across it toward the other side
+pub fn fxaa(dt: &mut DrawTarget) {
+    let (width, height) = (dt.width() as usize, dt.height() as usize);
+    if width < 3 || height < 3 {
+        return;
+    }
+
+    let source: Vec<u32> = dt.get_data().to_vec();
+    let luma: Vec<f32> = source.iter().map(|pixel| luma(*pixel)).collect();
+    let out = dt.get_data_mut();
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let i = y * width + x;
+            let (m, n, s, e, w) = (
+                luma[i],
+                luma[i - width],
+                luma[i + width],
+                luma[i + 1],
+                luma[i - 1],
+            );
+
+            let max = m.max(n).max(s).max(e).max(w);
+            let min = m.min(n).min(s).min(e).min(w);
+            let range = max - min;
+            if range < FXAA_THRESHOLD_MIN.max(max * FXAA_THRESHOLD) {
+                continue;
+            }
+
+            let (nw, ne) = (luma[i - width - 1], luma[i - width + 1]);
+            let (sw, se) = (luma[i + width - 1], luma[i + width + 1]);
+
+            // how much brighter or darker the pixel is than its surroundings
+            let average = (2. * (n + s + e + w) + nw + ne + sw + se) / 12.;
+            let subpixel = ((average - m).abs() / range).clamp(0., 1.);
+            let subpixel = subpixel * subpixel * (3. - 2. * subpixel);
+            let blend = (subpixel * subpixel * 0.75).max(0.25);
+
+            let horizontal =
+                (nw + sw - 2. * w).abs() + (n + s - 2. * m).abs() * 2. + (ne + se - 2. * e).abs();
+            let vertical =
+                (nw + ne - 2. * n).abs() + (w + e - 2. * m).abs() * 2. + (sw + se - 2. * s).abs();
+
+            // the neighbour on the other side of the edge
+            let across = match horizontal >= vertical {
+                true if (n - m).abs() >= (s - m).abs() => i - width,
+                true => i + width,
+                false if (w - m).abs() >= (e - m).abs() => i - 1,
+                false => i + 1,
+            };
+
+            out[i] = lerp_argb(source[i], source[across], blend);
+        }
+    }
+}
+
+fn luma(pixel: u32) -> f32 {
+    let r = (pixel >> 16 & 0xff) as f32;
+    let g = (pixel >> 8 & 0xff) as f32;
+    let b = (pixel & 0xff) as f32;
+
+    (0.299 * r + 0.587 * g + 0.114 * b) / 255.
+}
+
+fn lerp_argb(from: u32, to: u32, t: f32) -> u32 {
+    (0..4).fold(0, |argb, channel| {
+        let shift = 24 - channel * 8;
+        let a = (from >> shift & 0xff) as f32;
+        let b = (to >> shift & 0xff) as f32;
+        argb << 8 | (a + (b - a) * t).round() as u32
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::base::Vector4;
+
+    fn triangle(corners: [(f32, f32, f32); 3], color: SolidSource) -> Triangle {
+        Triangle {
+            vertices: corners.map(|(x, y, z)| Vector4::new(x, y, z, 1.)),
+            color,
+            ..Default::default()
+        }
+    }
+
+    // long shallow edges, one face in front of another and one cutting through
+    // both in depth, so coverage and the per sample depth test both matter
+    fn scene() -> Vec<Triangle> {
+        let color = |r, g, b| SolidSource { r, g, b, a: 0xff };
+        vec![
+            triangle(
+                [(2., 3., 0.5), (37., 9., 0.5), (6., 36., 0.5)],
+                color(0xe0, 0x30, 0x30),
+            ),
+            triangle(
+                [(10., 30., 0.2), (38., 20., 0.2), (20., 1., 0.2)],
+                color(0x30, 0x50, 0xe0),
+            ),
+            Triangle {
+                vertex_colors: Some([
+                    color(0x20, 0xf0, 0x20),
+                    color(0xf0, 0xf0, 0x20),
+                    color(0x20, 0x90, 0x90),
+                ]),
+                ..triangle(
+                    [(0., 38., 0.1), (39., 31., 0.9), (28., 39.5, 0.1)],
+                    color(0, 0, 0),
+                )
+            },
+        ]
+    }
+
+    // the resolved image, and how many colors it took to get there
+    fn rasterize(new: impl Fn(&DrawTarget) -> Rasterizer) -> (Vec<u32>, usize) {
+        let mut dt = DrawTarget::new(40, 40);
+        dt.get_data_mut().fill(0xff00_0000);
+        let mut rasterizer = new(&dt);
+        for triangle in scene().iter() {
+            rasterizer.draw(triangle, &RenderSettings::default());
+        }
+        rasterizer.resolve(&mut dt);
+
+        (dt.get_data().to_vec(), rasterizer.shaded())
+    }
+
+    // mean difference per channel, 0..255
+    fn error(image: &[u32], reference: &[u32]) -> f32 {
+        let total: u32 = image
+            .iter()
+            .zip(reference)
+            .map(|(a, b)| {
+                (0..3)
+                    .map(|channel| (a >> (channel * 8) & 0xff).abs_diff(b >> (channel * 8) & 0xff))
+                    .sum::<u32>()
+            })
+            .sum();
+        total as f32 / (image.len() * 3) as f32
+    }
+
+    // the trade off between the modes: error against a 16x supersampled reference
+    // for quality, colors worked out for cost. run with --nocapture to see them
+    #[test]
+    fn anti_aliasing_trades_shading_for_quality() {
+        let (reference, _) = rasterize(|dt| Rasterizer::with_samples(16, &CENTER, dt));
+        let measure = |mode: AntiAliasing| {
+            let (image, shaded) = rasterize(|dt| Rasterizer::new(mode, dt));
+            let error = error(&image, &reference);
+            println!("{:>7}  error {:.3}  shaded {}", mode.name(), error, shaded);
+            (error, shaded)
+        };
+        let off = measure(AntiAliasing::Off);
+        let ssaa2x = measure(AntiAliasing::Ssaa2x);
+        let ssaa4x = measure(AntiAliasing::Ssaa4x);
+        let msaa4x = measure(AntiAliasing::Msaa4x);
+
+        assert!(ssaa4x.0 < ssaa2x.0 && ssaa2x.0 < off.0);
+        assert!(msaa4x.0 < off.0 * 0.6);
+        // msaa only shades the extra pixels its samples reach along the edges
+        assert!(ssaa4x.1 > ssaa2x.1 && ssaa2x.1 > 3 * off.1);
+        assert!(msaa4x.1 < off.1 * 5 / 4);
+    }
+}
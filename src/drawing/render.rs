@@ -1,13 +1,20 @@
+use std::time::Instant;
 use std::vec;
 
+use crate::drawing::raster::{fxaa, Rasterizer};
+use crate::drawing::settings::{AntiAliasing, RenderMode, RenderSettings};
 use crate::drawing::shapes::draw_triangle;
+use crate::drawing::stats::FrameStats;
 use crate::linear_algebra::clipping::clip_against_window;
+use crate::linear_algebra::queue::{NormalLines, SelectionLines};
 use crate::linear_algebra::triangles::Triangle;
 use crate::{HEIGHT, WIDTH};
 use minifb::Window;
+use nalgebra::base::Vector4;
 use nannou::Draw;
 use raqote::{
-    DrawOptions, DrawTarget, LineCap, LineJoin, PathBuilder, SolidSource, Source, StrokeStyle,
+    DrawOptions, DrawTarget, ExtendMode, FilterMode, Image, LineCap, LineJoin, Path, PathBuilder,
+    SolidSource, Source, StrokeStyle, Transform,
 };
 
 use super::shapes::_draw_triangle_nannou;
@@ -25,17 +32,56 @@ pub fn _render_nannou(triangles: Vec<Triangle>, draw: &Draw) {
     }
 }
 
-pub fn render(window: &mut Window, triangle_queue: Vec<Triangle>, dt: &mut DrawTarget) {
+pub fn render(
+    triangle_queue: Vec<Triangle>,
+    dt: &mut DrawTarget,
+    settings: &RenderSettings,
+    stats: &mut FrameStats,
+) {
+    let start = Instant::now();
+
+    // raqote fills a path with a single color, so vertex colored meshes
+    // always go through the rasterizer to get blended
+    let blended = triangle_queue.iter().any(|t| t.vertex_colors.is_some());
+    let mut rasterizer: Option<Rasterizer> = match settings.anti_aliasing {
+        AntiAliasing::Vector if blended => Some(Rasterizer::new(AntiAliasing::Off, dt)),
+        AntiAliasing::Vector => None,
+        mode => Some(Rasterizer::new(mode, dt)),
+    };
+    // the background as drawn, for wireframe faces to paint back over hidden lines
+    let background: Vec<u32> = match settings.mode {
+        RenderMode::Wireframe if rasterizer.is_none() => dt.get_data().to_vec(),
+        _ => Vec::new(),
+    };
+
     for triangle in triangle_queue.iter() {
         let mut clipped: [Triangle; 2] = [Triangle::default(), Triangle::default()];
         let mut vec_of_triangles: Vec<Triangle> = Vec::new();
 
         clip_against_window(&mut clipped, &mut vec_of_triangles, triangle);
 
+        stats.drawn += vec_of_triangles.len();
+
         for final_triangle in vec_of_triangles.iter() {
-            render_triangle(final_triangle, dt);
+            match rasterizer.as_mut() {
+                Some(rasterizer) => rasterizer.draw(final_triangle, settings),
+                None => render_triangle(final_triangle, dt, settings, &background),
+            }
         }
     }
+
+    if let Some(rasterizer) = rasterizer {
+        rasterizer.resolve(dt);
+        stats.shaded += rasterizer.shaded();
+    }
+    if settings.fxaa {
+        fxaa(dt);
+    }
+    stats.raster_time += start.elapsed().as_secs_f32();
+}
+
+// shows the finished frame, after any overlays have been drawn on top
+pub fn present(window: &mut Window, dt: &DrawTarget) {
     window
         .update_with_buffer(dt.get_data(), WIDTH, HEIGHT)
         .unwrap();
@@ -49,33 +95,138 @@ pub fn render(window: &mut Window, triangle_queue: Vec<Triangle>, dt: &mut DrawT
 //     a: 0xff,
 // }),
 
-pub fn render_triangle(triangle: &Triangle, dt: &mut DrawTarget) {
+pub fn render_triangle(
+    triangle: &Triangle,
+    dt: &mut DrawTarget,
+    settings: &RenderSettings,
+    background: &[u32],
+) {
     // Rasterize triangle
     let mut pb = PathBuilder::new();
 
     draw_triangle(&mut pb, &triangle);
     let path = pb.finish();
 
-    // color in shape
-    dt.fill(&path, &Source::Solid(triangle.color), &DrawOptions::new());
+    match settings.mode {
+        RenderMode::Shaded | RenderMode::Normals => {
+            // color in shape
+            dt.fill(&path, &Source::Solid(triangle.color), &DrawOptions::new());
+        }
+        RenderMode::Wireframe => {
+            // painting the background back over what's behind keeps hidden lines hidden
+            let image = Image {
+                width: WIDTH as i32,
+                height: HEIGHT as i32,
+                data: background,
+            };
+            let source = Source::Image(
+                image,
+                ExtendMode::Pad,
+                FilterMode::Nearest,
+                Transform::identity(),
+            );
+            dt.fill(&path, &source, &DrawOptions::new());
+            stroke_path(&path, dt, settings);
+        }
+        RenderMode::ShadedWireframe => {
+            dt.fill(&path, &Source::Solid(triangle.color), &DrawOptions::new());
+            stroke_path(&path, dt, settings);
+        }
+        RenderMode::Points => {
+            let size = settings.point_size;
+            for vertex in triangle.vertices.iter() {
+                dt.fill_rect(
+                    vertex.x - size * 0.5,
+                    vertex.y - size * 0.5,
+                    size,
+                    size,
+                    &Source::Solid(triangle.color),
+                    &DrawOptions::new(),
+                );
+            }
+        }
+    }
+}
 
-    // coloring in wireframe
+// coloring in wireframe
+fn stroke_path(path: &Path, dt: &mut DrawTarget, settings: &RenderSettings) {
     dt.stroke(
-        &path,
-        &Source::Solid(SolidSource {
-            r: 0x18,
-            g: 0x15,
-            b: 0x15,
-            a: 0xff,
-        }),
-        &StrokeStyle {
-            cap: LineCap::Round,
-            join: LineJoin::Round,
-            width: 1.,
-            miter_limit: 2.,
-            dash_array: vec![1., 1.],
-            dash_offset: 0.,
+        path,
+        &Source::Solid(settings.line_color),
+        &line_style(settings),
+        &DrawOptions::new(),
+    );
+}
+
+fn line_style(settings: &RenderSettings) -> StrokeStyle {
+    StrokeStyle {
+        cap: LineCap::Round,
+        join: LineJoin::Round,
+        width: settings.line_width,
+        miter_limit: 2.,
+        dash_array: match settings.dashed_lines {
+            true => vec![1., 1.],
+            false => vec![],
         },
+        dash_offset: 0.,
+    }
+}
+
+pub fn render_normals(lines: &NormalLines, dt: &mut DrawTarget, settings: &RenderSettings) {
+    let style = StrokeStyle {
+        dash_array: vec![],
+        ..line_style(settings)
+    };
+
+    stroke_segments(&lines.faces, settings.face_normal_color, &style, dt);
+    stroke_segments(&lines.vertices, settings.vertex_normal_color, &style, dt);
+}
+
+// picked mesh's bounding box, with the picked triangle drawn thicker on top
+pub fn render_selection(lines: &SelectionLines, dt: &mut DrawTarget, settings: &RenderSettings) {
+    let style = StrokeStyle {
+        dash_array: vec![],
+        ..line_style(settings)
+    };
+    let thick = StrokeStyle {
+        width: settings.line_width + 1.,
+        ..style.clone()
+    };
+
+    stroke_segments(&lines.bounds, settings.selected_mesh_color, &style, dt);
+    stroke_segments(
+        &lines.triangle,
+        settings.selected_triangle_color,
+        &thick,
+        dt,
+    );
+}
+
+// bvh node boxes, solid so nested boxes stay readable
+pub fn render_boxes(lines: &[[Vector4<f32>; 2]], dt: &mut DrawTarget, settings: &RenderSettings) {
+    let style = StrokeStyle {
+        dash_array: vec![],
+        ..line_style(settings)
+    };
+
+    stroke_segments(lines, settings.bvh_color, &style, dt);
+}
+
+fn stroke_segments(
+    segments: &[[Vector4<f32>; 2]],
+    color: SolidSource,
+    style: &StrokeStyle,
+    dt: &mut DrawTarget,
+) {
+    let mut pb = PathBuilder::new();
+    for [start, end] in segments.iter() {
+        pb.move_to(start.x, start.y);
+        pb.line_to(end.x, end.y);
+    }
+    dt.stroke(
+        &pb.finish(),
+        &Source::Solid(color),
+        style,
         &DrawOptions::new(),
     );
 }
@@ -0,0 +1,177 @@
+use raqote::SolidSource;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    Shaded,
+    // outlines only, faces are filled with the clear color so hidden lines stay hidden
+    Wireframe,
+    ShadedWireframe,
+    // just the vertices of visible triangles
+    Points,
+    // shaded, with face normals and vertex normals drawn as line segments
+    Normals,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 5] = [
+        RenderMode::Shaded,
+        RenderMode::Wireframe,
+        RenderMode::ShadedWireframe,
+        RenderMode::Points,
+        RenderMode::Normals,
+    ];
+
+    pub fn next(&self) -> RenderMode {
+        let index = RenderMode::ALL
+            .iter()
+            .position(|mode| mode == self)
+            .unwrap_or(0);
+        RenderMode::ALL[(index + 1) % RenderMode::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderMode::Shaded => "shaded",
+            RenderMode::Wireframe => "wireframe",
+            RenderMode::ShadedWireframe => "shaded+wire",
+            RenderMode::Points => "points",
+            RenderMode::Normals => "normals",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AntiAliasing {
+    // raqote's path filling, with its own coverage based edge smoothing
+    Vector,
+    // software rasterizer with a depth buffer, one sample per pixel
+    Off,
+    // software rasterizer at 2x or 4x the resolution on each axis, box filtered down
+    Ssaa2x,
+    Ssaa4x,
+    // software rasterizer with 4 depth tested samples per pixel on a rotated grid,
+    // shaded once per pixel. edges come out about as smooth as ssaa2x for a quarter of
+    // the shading, but nothing inside a face is smoothed
+    Msaa4x,
+}
+
+impl AntiAliasing {
+    pub const ALL: [AntiAliasing; 5] = [
+        AntiAliasing::Vector,
+        AntiAliasing::Off,
+        AntiAliasing::Ssaa2x,
+        AntiAliasing::Ssaa4x,
+        AntiAliasing::Msaa4x,
+    ];
+
+    pub fn next(&self) -> AntiAliasing {
+        let index = AntiAliasing::ALL
+            .iter()
+            .position(|mode| mode == self)
+            .unwrap_or(0);
+        AntiAliasing::ALL[(index + 1) % AntiAliasing::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AntiAliasing::Vector => "vector",
+            AntiAliasing::Off => "off",
+            AntiAliasing::Ssaa2x => "ssaa2x",
+            AntiAliasing::Ssaa4x => "ssaa4x",
+            AntiAliasing::Msaa4x => "msaa4x",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub mode: RenderMode,
+    // mode to go back to when wireframe is toggled off
+    pub solid_mode: RenderMode,
+    pub line_color: SolidSource,
+    pub line_width: f32,
+    pub dashed_lines: bool,
+    // size of the squares drawn in points mode
+    pub point_size: f32,
+    // length of debug normals in world units
+    pub normal_length: f32,
+    pub face_normal_color: SolidSource,
+    pub vertex_normal_color: SolidSource,
+    // outline of a picked triangle and the box around its mesh
+    pub selected_triangle_color: SolidSource,
+    pub selected_mesh_color: SolidSource,
+    // draws the bvh node boxes at this depth
+    pub bvh_depth: Option<usize>,
+    pub bvh_color: SolidSource,
+    pub anti_aliasing: AntiAliasing,
+    // luma based edge blur run over the finished frame, works with any anti_aliasing
+    pub fxaa: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            mode: RenderMode::ShadedWireframe,
+            solid_mode: RenderMode::ShadedWireframe,
+            line_color: SolidSource {
+                r: 0x18,
+                g: 0x15,
+                b: 0x15,
+                a: 0xff,
+            },
+            line_width: 1.,
+            dashed_lines: true,
+            point_size: 2.,
+            normal_length: 0.3,
+            face_normal_color: SolidSource {
+                r: 0x7a,
+                g: 0xd6,
+                b: 0x67,
+                a: 0xff,
+            },
+            vertex_normal_color: SolidSource {
+                r: 0x67,
+                g: 0x9a,
+                b: 0xd6,
+                a: 0xff,
+            },
+            selected_triangle_color: SolidSource {
+                r: 0xf2,
+                g: 0xd0,
+                b: 0x4b,
+                a: 0xff,
+            },
+            selected_mesh_color: SolidSource {
+                r: 0xf4,
+                g: 0xdc,
+                b: 0xd7,
+                a: 0xff,
+            },
+            bvh_depth: None,
+            bvh_color: SolidSource {
+                r: 0x67,
+                g: 0xd6,
+                b: 0xc4,
+                a: 0xff,
+            },
+            anti_aliasing: AntiAliasing::Vector,
+            fxaa: false,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn cycle_mode(&mut self) {
+        self.mode = self.mode.next();
+        if self.mode != RenderMode::Wireframe {
+            self.solid_mode = self.mode;
+        }
+    }
+
+    pub fn toggle_wireframe(&mut self) {
+        self.mode = match self.mode {
+            RenderMode::Wireframe => self.solid_mode,
+            _ => RenderMode::Wireframe,
+        };
+    }
+}
@@ -0,0 +1,46 @@
+// counters for the HUD, reset at the start of every frame
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    // real seconds the last frame took
+    pub frame_time: f32,
+    // smoothed so the number is readable
+    pub fps: f32,
+    // triangles in the mesh
+    pub submitted: usize,
+    // back faces and triangles outside the view thrown away before clipping
+    pub culled: usize,
+    // triangles cut by the near plane or the screen edges
+    pub clipped: usize,
+    // triangles that made it to the rasterizer, after clipping
+    pub drawn: usize,
+    // seconds spent drawing the queue, anti aliasing and fxaa included
+    pub raster_time: f32,
+    // face colors the software rasterizer worked out, once per sample with ssaa
+    // and once per pixel with msaa. 0 for raqote's vector path
+    pub shaded: usize,
+    // level of detail drawn out of how many, and whether it was fixed rather
+    // than picked by size. set by whoever picks it
+    pub lod: usize,
+    pub lod_levels: usize,
+    pub lod_fixed: bool,
+}
+
+impl FrameStats {
+    pub fn begin_frame(&mut self, frame_time: f32) {
+        self.frame_time = frame_time;
+        if frame_time > 0. {
+            let fps = 1. / frame_time;
+            self.fps = match self.fps > 0. {
+                true => self.fps * 0.9 + fps * 0.1,
+                false => fps,
+            };
+        }
+
+        self.submitted = 0;
+        self.culled = 0;
+        self.clipped = 0;
+        self.drawn = 0;
+        self.raster_time = 0.;
+        self.shaded = 0;
+    }
+}
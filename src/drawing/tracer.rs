@@ -0,0 +1,323 @@
+use image::RgbaImage;
+use nalgebra::base::{Matrix4, Vector4};
+use raqote::SolidSource;
+
+use crate::camera::lightsource::{light_color, light_dir};
+use crate::camera::view::Camera;
+use crate::drawing::background::Background;
+use crate::drawing::colors::mix_colors;
+use crate::drawing::fog::FogSettings;
+use crate::linear_algebra::bvh::Bvh;
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::matrices::transform_normal;
+use crate::linear_algebra::ray::{Pick, Ray};
+use crate::linear_algebra::vectors::{
+    add_vec, cross_product, dot_product, mult_vec, normalize_vec, sub_vec,
+};
+use crate::{HEIGHT, WIDTH};
+
+// moves secondary rays off the surface so they don't hit the triangle they start on
+const SURFACE_OFFSET: f32 = 1e-3;
+// how much light a diffuse bounce brings back, on top of the direct light
+const BOUNCE_STRENGTH: f32 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct TraceSettings {
+    // rays per pixel, the first through the centre and the rest jittered
+    pub samples: u32,
+    // reflection and diffuse bounces per primary ray
+    pub max_bounces: u32,
+    pub shadows: bool,
+    // 0 is matte, 1 a perfect mirror
+    pub reflectivity: f32,
+    // one cosine weighted random bounce per hit for indirect light
+    pub diffuse_bounce: bool,
+    // same seed, same image
+    pub seed: u64,
+}
+
+impl Default for TraceSettings {
+    fn default() -> Self {
+        TraceSettings {
+            samples: 4,
+            max_bounces: 2,
+            shadows: true,
+            reflectivity: 0.,
+            diffuse_bounce: false,
+            seed: 1,
+        }
+    }
+}
+
+// everything get_triangle_queue draws with, so the two renderers see the same thing
+pub struct Scene<'a> {
+    pub mesh: &'a Mesh,
+    // what the mesh is drawn with, corner normals are turned by it
+    pub world_matrix: &'a Matrix4<f32>,
+    // built or refit against the transform the mesh is drawn with
+    pub bvh: &'a Bvh,
+    pub camera: &'a Camera,
+    pub projection_matrix: &'a Matrix4<f32>,
+    pub background: &'a Background,
+    pub fog: &'a FogSettings,
+}
+
+// offline reference render at the window's size, slow but with shadows and bounces
+pub fn trace_image(scene: &Scene, settings: &TraceSettings) -> RgbaImage {
+    let mut sampler = Sampler::new(settings.seed);
+    let samples = settings.samples.max(1);
+    let mut image = RgbaImage::new(WIDTH as u32, HEIGHT as u32);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let mut sum = [0.; 3];
+        for sample in 0..samples {
+            let (jitter_x, jitter_y) = match sample {
+                0 => (0.5, 0.5),
+                _ => (sampler.next(), sampler.next()),
+            };
+            let ray = Ray::from_screen(
+                x as f32 + jitter_x,
+                y as f32 + jitter_y,
+                scene.camera,
+                scene.projection_matrix,
+            );
+
+            let color = trace(scene, &ray, settings, settings.max_bounces, &mut sampler);
+            for channel in 0..3 {
+                sum[channel] += color[channel];
+            }
+        }
+
+        let channel = |total: f32| (total / samples as f32 * 255.).round().clamp(0., 255.) as u8;
+        pixel.0 = [channel(sum[0]), channel(sum[1]), channel(sum[2]), 0xff];
+    }
+
+    image
+}
+
+// color along a ray, 0..1 per channel
+fn trace(
+    scene: &Scene,
+    ray: &Ray,
+    settings: &TraceSettings,
+    bounces: u32,
+    sampler: &mut Sampler,
+) -> [f32; 3] {
+    let hit = match scene.bvh.intersect_ray(ray) {
+        Some(hit) => hit,
+        None => return rgb(scene.background.sample(&ray.dir)),
+    };
+    let base_color = surface_color(scene, &hit);
+    let origin = add_vec(&hit.position, &mult_vec(&hit.normal, SURFACE_OFFSET));
+
+    let light = light_dir();
+    let mut light_dot = dot_product(&shading_normal(scene, &hit), &light);
+    if settings.shadows && light_dot > 0. {
+        let shadow = Ray { origin, dir: light };
+        if scene.bvh.intersect_ray(&shadow).is_some() {
+            light_dot = 0.;
+        }
+    }
+    let mut color = rgb(light_color(base_color, light_dot));
+
+    if bounces > 0 && settings.diffuse_bounce {
+        let bounce = Ray {
+            origin,
+            dir: cosine_sample(&hit.normal, sampler),
+        };
+        let incoming = trace(scene, &bounce, settings, bounces - 1, sampler);
+        let albedo = rgb(light_color(base_color, 1.));
+        for channel in 0..3 {
+            color[channel] += albedo[channel] * incoming[channel] * BOUNCE_STRENGTH;
+        }
+    }
+
+    if bounces > 0 && settings.reflectivity > 0. {
+        let dir = sub_vec(
+            &ray.dir,
+            &mult_vec(&hit.normal, 2. * dot_product(&ray.dir, &hit.normal)),
+        );
+        let reflected = trace(scene, &Ray { origin, dir }, settings, bounces - 1, sampler);
+        for channel in 0..3 {
+            color[channel] += (reflected[channel] - color[channel]) * settings.reflectivity;
+        }
+    }
+
+    // fogged by view space depth like the rasterizer, only on what the camera sees directly
+    if bounces == settings.max_bounces {
+        let depth = dot_product(
+            &sub_vec(&hit.position, &scene.camera.position),
+            &scene.camera.look_dir(),
+        );
+        let amount = scene.fog.amount(depth);
+        let fog = rgb(scene.fog.color);
+        for channel in 0..3 {
+            color[channel] += (fog[channel] - color[channel]) * amount;
+        }
+    }
+
+    color
+}
+
+// the triangle's own color, or its corner colors blended at the hit point
+fn surface_color(scene: &Scene, hit: &Pick) -> Option<SolidSource> {
+    let triangle = &scene.mesh.triangles[hit.triangle];
+    let colors = match triangle.vertex_colors {
+        Some(colors) => colors,
+        None => return triangle.base_color,
+    };
+    let [w0, w1, w2] = match barycentric(scene, hit) {
+        Some(weights) => weights,
+        None => return Some(colors[0]),
+    };
+
+    let ab = mix_colors(colors[0], colors[1], w1 / (w0 + w1).max(f32::EPSILON));
+    Some(mix_colors(ab, colors[2], w2))
+}
+
+// the corner normals blended at the hit point, like the rasterizer lights each
+// corner, or the face's own normal for triangles without them
+fn shading_normal(scene: &Scene, hit: &Pick) -> Vector4<f32> {
+    let triangle = &scene.mesh.triangles[hit.triangle];
+    match (triangle.normals, barycentric(scene, hit)) {
+        (Some(normals), Some(weights)) => {
+            let blended = (0..3).fold(Vector4::new(0., 0., 0., 1.), |sum, i| {
+                add_vec(&sum, &mult_vec(&normals[i], weights[i]))
+            });
+            transform_normal(scene.world_matrix, &blended)
+        }
+        _ => hit.normal,
+    }
+}
+
+// weights of each corner at the hit point from the areas opposite them,
+// None for a triangle too thin to have any
+fn barycentric(scene: &Scene, hit: &Pick) -> Option<[f32; 3]> {
+    let [a, b, c] = scene.bvh.triangle(hit.triangle);
+    let area = |p: &Vector4<f32>, q: &Vector4<f32>| {
+        dot_product(
+            &cross_product(&sub_vec(p, &hit.position), &sub_vec(q, &hit.position)),
+            &hit.normal,
+        )
+    };
+    let (w0, w1, w2) = (area(b, c), area(c, a), area(a, b));
+    let total = w0 + w1 + w2;
+    if total.abs() < f32::EPSILON {
+        return None;
+    }
+
+    Some([w0 / total, w1 / total, w2 / total])
+}
+
+// the window ignores alpha, so this does too
+fn rgb(color: SolidSource) -> [f32; 3] {
+    [
+        color.r as f32 / 255.,
+        color.g as f32 / 255.,
+        color.b as f32 / 255.,
+    ]
+}
+
+// random direction around the normal, more likely the closer it is to the normal
+fn cosine_sample(normal: &Vector4<f32>, sampler: &mut Sampler) -> Vector4<f32> {
+    let helper = match normal.x.abs() > 0.9 {
+        true => Vector4::new(0., 1., 0., 1.),
+        false => Vector4::new(1., 0., 0., 1.),
+    };
+    let tangent = normalize_vec(&cross_product(&helper, normal));
+    let bitangent = cross_product(normal, &tangent);
+
+    let angle = 2. * std::f32::consts::PI * sampler.next();
+    let r_squared = sampler.next();
+    let r = r_squared.sqrt();
+
+    normalize_vec(&add_vec(
+        &add_vec(
+            &mult_vec(&tangent, r * angle.cos()),
+            &mult_vec(&bitangent, r * angle.sin()),
+        ),
+        &mult_vec(normal, (1. - r_squared).sqrt()),
+    ))
+}
+
+// xorshift64*, plenty for jitter and bounce directions
+struct Sampler {
+    state: u64,
+}
+
+impl Sampler {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0
+        Sampler { state: seed.max(1) }
+    }
+
+    // uniform in 0..1
+    fn next(&mut self) -> f32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+        (bits >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drawing::stats::FrameStats;
+    use crate::linear_algebra::matrices::{
+        create_projection_matrix, create_world_matrix, multiply_matrix_vec,
+    };
+    use crate::linear_algebra::queue::get_triangle_queue;
+    use crate::linear_algebra::vectors::scale_point;
+    use crate::meshes::cube::_get_cube_mesh;
+
+    // the primary ray through the middle of every rastered triangle has to come back
+    // to that same spot on screen, or the tracer and the raster draw different images
+    #[test]
+    fn primary_rays_hit_what_the_raster_draws() {
+        let mesh = _get_cube_mesh();
+        let theta = 0.7;
+        let trans_vec = Vector4::new(0.5, -0.3, 6., 1.);
+        let world_matrix = create_world_matrix(&theta, &trans_vec);
+        let bvh = Bvh::build(&mesh, &world_matrix);
+        let camera = Camera::default();
+        let projection_matrix = create_projection_matrix();
+
+        let queue = get_triangle_queue(
+            &world_matrix,
+            &camera,
+            &mesh,
+            projection_matrix,
+            &FogSettings::default(),
+            Some(&bvh),
+            &mut FrameStats::default(),
+        );
+        assert!(!queue.is_empty());
+
+        for triangle in queue.iter() {
+            let x = triangle.vertices.iter().map(|v| v.x).sum::<f32>() / 3.;
+            let y = triangle.vertices.iter().map(|v| v.y).sum::<f32>() / 3.;
+            let ray = Ray::from_screen(x, y, &camera, &projection_matrix);
+            let hit = bvh
+                .intersect_ray(&ray)
+                .expect("ray missed a rastered triangle");
+
+            let view = multiply_matrix_vec(&camera.view_matrix(), &hit.position);
+            let screen = scale_point(&multiply_matrix_vec(&projection_matrix, &view));
+            assert!(
+                (screen.x - x).abs() < 0.01 && (screen.y - y).abs() < 0.01,
+                "hit drawn at ({}, {}), raster drew ({}, {})",
+                screen.x,
+                screen.y,
+                x,
+                y
+            );
+        }
+
+        // nothing is drawn in the corner, so nothing is hit there
+        let ray = Ray::from_screen(0.5, 0.5, &camera, &projection_matrix);
+        assert!(bvh.intersect_ray(&ray).is_none());
+    }
+}
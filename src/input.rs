@@ -0,0 +1,3 @@
+pub(crate) mod actions;
+pub(crate) mod args;
+pub(crate) mod bindings;
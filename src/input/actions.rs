@@ -0,0 +1,160 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    StrafeLeft,
+    StrafeRight,
+    MoveUp,
+    MoveDown,
+    YawLeft,
+    YawRight,
+    RollLeft,
+    RollRight,
+    CaptureMouse,
+    OrbitPan,
+    ToggleCameraMode,
+    ToggleWalkMode,
+    Jump,
+    FrameMesh,
+    CycleFileCamera,
+    Pick,
+    ToggleTurntable,
+    Pause,
+    StepFrame,
+    SlowDown,
+    SpeedUp,
+    CycleRenderMode,
+    ToggleWireframe,
+    CycleAntiAliasing,
+    ToggleFxaa,
+    CycleFog,
+    CycleBackground,
+    CycleBvhDepth,
+    ToggleSmoothNormals,
+    UnifyWinding,
+    FlipWinding,
+    CycleLod,
+    ToggleSubdivision,
+    Screenshot,
+    RayTrace,
+    ExportMesh,
+    ExportStl,
+    ExportPly,
+    ToggleRecording,
+    ToggleGifRecording,
+    Reset,
+    ToggleHud,
+    ToggleHelp,
+    Quit,
+}
+
+impl Action {
+    // in the order they're listed in the help overlay
+    pub const ALL: [Action; 46] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::YawLeft,
+        Action::YawRight,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::CaptureMouse,
+        Action::OrbitPan,
+        Action::ToggleCameraMode,
+        Action::ToggleWalkMode,
+        Action::Jump,
+        Action::FrameMesh,
+        Action::CycleFileCamera,
+        Action::Pick,
+        Action::ToggleTurntable,
+        Action::Pause,
+        Action::StepFrame,
+        Action::SlowDown,
+        Action::SpeedUp,
+        Action::CycleRenderMode,
+        Action::ToggleWireframe,
+        Action::CycleAntiAliasing,
+        Action::ToggleFxaa,
+        Action::CycleFog,
+        Action::CycleBackground,
+        Action::CycleBvhDepth,
+        Action::ToggleSmoothNormals,
+        Action::UnifyWinding,
+        Action::FlipWinding,
+        Action::CycleLod,
+        Action::ToggleSubdivision,
+        Action::Screenshot,
+        Action::RayTrace,
+        Action::ExportMesh,
+        Action::ExportStl,
+        Action::ExportPly,
+        Action::ToggleRecording,
+        Action::ToggleGifRecording,
+        Action::Reset,
+        Action::ToggleHud,
+        Action::ToggleHelp,
+        Action::Quit,
+    ];
+
+    // name used in the bindings file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBack => "move_back",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::YawLeft => "yaw_left",
+            Action::YawRight => "yaw_right",
+            Action::RollLeft => "roll_left",
+            Action::RollRight => "roll_right",
+            Action::CaptureMouse => "capture_mouse",
+            Action::OrbitPan => "orbit_pan",
+            Action::ToggleCameraMode => "toggle_camera_mode",
+            Action::ToggleWalkMode => "toggle_walk_mode",
+            Action::Jump => "jump",
+            Action::FrameMesh => "frame_mesh",
+            Action::CycleFileCamera => "cycle_file_camera",
+            Action::Pick => "pick",
+            Action::ToggleTurntable => "toggle_turntable",
+            Action::Pause => "pause",
+            Action::StepFrame => "step_frame",
+            Action::SlowDown => "slow_down",
+            Action::SpeedUp => "speed_up",
+            Action::CycleRenderMode => "cycle_render_mode",
+            Action::ToggleWireframe => "toggle_wireframe",
+            Action::CycleAntiAliasing => "cycle_anti_aliasing",
+            Action::ToggleFxaa => "toggle_fxaa",
+            Action::CycleFog => "cycle_fog",
+            Action::CycleBackground => "cycle_background",
+            Action::CycleBvhDepth => "cycle_bvh_depth",
+            Action::ToggleSmoothNormals => "toggle_smooth_normals",
+            Action::UnifyWinding => "unify_winding",
+            Action::FlipWinding => "flip_winding",
+            Action::CycleLod => "cycle_lod",
+            Action::ToggleSubdivision => "toggle_subdivision",
+            Action::Screenshot => "screenshot",
+            Action::RayTrace => "ray_trace",
+            Action::ExportMesh => "export_mesh",
+            Action::ExportStl => "export_stl",
+            Action::ExportPly => "export_ply",
+            Action::ToggleRecording => "toggle_recording",
+            Action::ToggleGifRecording => "toggle_gif_recording",
+            Action::Reset => "reset",
+            Action::ToggleHud => "toggle_hud",
+            Action::ToggleHelp => "toggle_help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+}
@@ -0,0 +1,144 @@
+// what the viewer shows, picked by the command line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    // nothing was given
+    Cube,
+    // a model file or archive, watched while the viewer runs
+    Model(String),
+    // `--terrain [seed]`
+    Noise(Option<u32>),
+    // `--heightmap <png>`
+    Heightmap(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub subject: Subject,
+    // `--slope`, shades terrain by steepness rather than height
+    pub slope: bool,
+    // `--skybox <dir>`
+    pub skybox: Option<String>,
+    // `--weld <eps>`, in world units once prepare_mesh has sized the model
+    pub weld: Option<f32>,
+    // `--decimate <n>`, triangles to simplify a model down to
+    pub decimate: Option<usize>,
+}
+
+pub const USAGE: &str = concat!(
+    "usage: childs_play [model | --terrain [seed] | --heightmap <png>]\n",
+    "                   [--slope] [--skybox <dir>] [--weld <eps>] [--decimate <n>]"
+);
+
+impl Args {
+    // options can go anywhere, whatever's left has to name one subject and nothing more
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args {
+            subject: Subject::Cube,
+            slope: false,
+            skybox: None,
+            weld: None,
+            decimate: None,
+        };
+        let mut rest: Vec<String> = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--slope" => parsed.slope = true,
+                "--skybox" => parsed.skybox = Some(value(&mut args, "--skybox", "a directory")?),
+                "--weld" => {
+                    let eps = value(&mut args, "--weld", "a distance")?;
+                    parsed.weld = match eps.parse::<f32>() {
+                        Ok(eps) if eps > 0. => Some(eps),
+                        _ => return Err(format!("{} isn't a weld distance", eps)),
+                    };
+                }
+                "--decimate" => {
+                    let target = value(&mut args, "--decimate", "a triangle count")?;
+                    parsed.decimate = Some(
+                        target
+                            .parse()
+                            .map_err(|_| format!("{} isn't a triangle count", target))?,
+                    );
+                }
+                _ => rest.push(arg),
+            }
+        }
+
+        parsed.subject = match rest.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+            [] => Subject::Cube,
+            ["--terrain"] => Subject::Noise(None),
+            ["--terrain", seed] => Subject::Noise(Some(
+                seed.parse().map_err(|_| format!("{} isn't a seed", seed))?,
+            )),
+            ["--heightmap"] => return Err(String::from("--heightmap needs a png")),
+            ["--heightmap", file_path] => Subject::Heightmap(file_path.to_string()),
+            ["--terrain" | "--heightmap", _, ref extra @ ..] => {
+                return Err(format!("unexpected {}", extra.join(" ")))
+            }
+            [flag, ..] if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            [file_path] => Subject::Model(file_path.to_string()),
+            [_, ref extra @ ..] => return Err(format!("unexpected {}", extra.join(" "))),
+        };
+
+        Ok(parsed)
+    }
+}
+
+// the argument after an option that takes one
+fn value(
+    args: &mut impl Iterator<Item = String>,
+    option: &str,
+    what: &str,
+) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} needs {}", option, what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        Args::parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn options_go_anywhere_around_the_subject() {
+        let args = parse("--slope --terrain 3 --skybox sky").unwrap();
+        assert_eq!(args.subject, Subject::Noise(Some(3)));
+        assert!(args.slope);
+        assert_eq!(args.skybox.as_deref(), Some("sky"));
+        assert_eq!(args.weld, None);
+
+        let args = parse("--weld 1e-4 part.stl --decimate 500").unwrap();
+        assert_eq!(args.subject, Subject::Model(String::from("part.stl")));
+        assert_eq!(args.weld, Some(1e-4));
+        assert_eq!(args.decimate, Some(500));
+
+        assert_eq!(parse("").unwrap().subject, Subject::Cube);
+        assert_eq!(parse("--terrain").unwrap().subject, Subject::Noise(None));
+        assert_eq!(
+            parse("teapot.obj").unwrap().subject,
+            Subject::Model(String::from("teapot.obj"))
+        );
+    }
+
+    #[test]
+    fn extra_or_broken_arguments_are_refused() {
+        for line in [
+            "--terrain 3 extra",
+            "--terrain x",
+            "--heightmap",
+            "--heightmap a.png b.png",
+            "teapot.obj cube.obj",
+            "--skybox",
+            "--weld -1 part.stl",
+            "part.stl --weld",
+            "part.stl --decimate half",
+            "--wobble",
+        ] {
+            assert!(parse(line).is_err(), "{} was accepted", line);
+        }
+    }
+}
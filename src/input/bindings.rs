@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fs;
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::input::actions::Action;
+use crate::meshes::load_error::LoadError;
+
+// every key a binding can name, matched against its Debug name
+#[rustfmt::skip]
+const KEYS: [Key; 106] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+    Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+    Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up,
+    Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal,
+    Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket, Key::Semicolon,
+    Key::Slash, Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape,
+    Key::Home, Key::Insert, Key::Menu, Key::PageDown, Key::PageUp, Key::Pause,
+    Key::Space, Key::Tab, Key::NumLock, Key::CapsLock, Key::ScrollLock,
+    Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+    Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus,
+    Key::NumPadPlus, Key::NumPadEnter,
+    Key::LeftAlt, Key::RightAlt, Key::LeftSuper, Key::RightSuper,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    pub fn held(window: &Window) -> Self {
+        Modifiers {
+            ctrl: window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl),
+            shift: window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift),
+            alt: window.is_key_down(Key::LeftAlt) || window.is_key_down(Key::RightAlt),
+        }
+    }
+
+    // every modifier in self is also in other
+    fn within(&self, other: &Modifiers) -> bool {
+        (!self.ctrl || other.ctrl) && (!self.shift || other.shift) && (!self.alt || other.alt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl Binding {
+    // "W", "Ctrl+Shift+S", "Key1"
+    pub fn parse(text: &str) -> Option<Binding> {
+        let mut modifiers = Modifiers::default();
+        let mut parts: Vec<&str> = text.split('+').map(|part| part.trim()).collect();
+        let key_name = parts.pop()?;
+
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return None,
+            }
+        }
+
+        Some(Binding {
+            key: parse_key(key_name)?,
+            modifiers,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.ctrl {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.shift {
+            label.push_str("Shift+");
+        }
+        if self.modifiers.alt {
+            label.push_str("Alt+");
+        }
+        label.push_str(&format!("{:?}", self.key));
+
+        label
+    }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    // bare digits are friendlier than minifb's Key0..Key9
+    let name = match name.len() == 1 && name.chars().all(|c| c.is_ascii_digit()) {
+        true => format!("Key{}", name),
+        false => name.to_string(),
+    };
+
+    KEYS.iter()
+        .copied()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(&name))
+}
+
+pub struct Bindings {
+    map: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let defaults: [(Action, &[&str]); 46] = [
+            (Action::MoveForward, &["W"]),
+            (Action::MoveBack, &["S"]),
+            (Action::StrafeLeft, &["Left"]),
+            (Action::StrafeRight, &["Right"]),
+            (Action::MoveUp, &["Up"]),
+            (Action::MoveDown, &["Down"]),
+            (Action::YawLeft, &["A"]),
+            (Action::YawRight, &["D"]),
+            (Action::RollLeft, &["Q"]),
+            (Action::RollRight, &["E"]),
+            (Action::CaptureMouse, &["M"]),
+            (Action::OrbitPan, &["LeftShift", "RightShift"]),
+            (Action::ToggleCameraMode, &["C"]),
+            (Action::ToggleWalkMode, &["G"]),
+            (Action::Jump, &["Space"]),
+            (Action::FrameMesh, &["F"]),
+            (Action::CycleFileCamera, &["J"]),
+            (Action::Pick, &["I"]),
+            (Action::ToggleTurntable, &["T"]),
+            (Action::Pause, &["P"]),
+            (Action::StepFrame, &["N"]),
+            (Action::SlowDown, &["Minus"]),
+            (Action::SpeedUp, &["Equal"]),
+            (Action::CycleRenderMode, &["R"]),
+            (Action::ToggleWireframe, &["Z"]),
+            (Action::CycleAntiAliasing, &["X"]),
+            (Action::ToggleFxaa, &["Shift+X"]),
+            (Action::CycleFog, &["O"]),
+            (Action::CycleBackground, &["Y"]),
+            (Action::CycleBvhDepth, &["B"]),
+            (Action::ToggleSmoothNormals, &["L"]),
+            (Action::UnifyWinding, &["U"]),
+            (Action::FlipWinding, &["Shift+U"]),
+            (Action::CycleLod, &["K"]),
+            (Action::ToggleSubdivision, &["V"]),
+            (Action::Screenshot, &["F12"]),
+            (Action::RayTrace, &["F9"]),
+            (Action::ExportMesh, &["F11"]),
+            (Action::ExportStl, &["Shift+F11"]),
+            (Action::ExportPly, &["Ctrl+F11"]),
+            (Action::ToggleRecording, &["F10"]),
+            (Action::ToggleGifRecording, &["Shift+F10"]),
+            (Action::Reset, &["Tab"]),
+            (Action::ToggleHud, &["F3"]),
+            (Action::ToggleHelp, &["H", "F1"]),
+            (Action::Quit, &["Escape"]),
+        ];
+
+        let map = defaults
+            .iter()
+            .map(|(action, keys)| {
+                let parsed = keys
+                    .iter()
+                    .map(|key| Binding::parse(key).unwrap())
+                    .collect();
+                (*action, parsed)
+            })
+            .collect();
+
+        Bindings { map }
+    }
+}
+
+impl Bindings {
+    // one action per line, `action = binding, binding`, # starts a comment.
+    // actions left out of the file keep their default bindings
+    pub fn load(file_path: &str) -> Result<Bindings, LoadError> {
+        let mut bindings = Bindings::default();
+        let contents = fs::read_to_string(file_path)?;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parse_error =
+                |msg: &str| LoadError::Parse(format!("{}:{}: {}", file_path, number + 1, msg));
+
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| parse_error("expected `action = key`"))?;
+            let action = Action::from_name(name.trim())
+                .ok_or_else(|| parse_error(&format!("unknown action `{}`", name.trim())))?;
+
+            let mut parsed: Vec<Binding> = Vec::new();
+            for key in keys
+                .split(',')
+                .map(|key| key.trim())
+                .filter(|key| !key.is_empty())
+            {
+                let binding = Binding::parse(key)
+                    .ok_or_else(|| parse_error(&format!("unknown key `{}`", key)))?;
+                parsed.push(binding);
+            }
+
+            bindings.map.insert(action, parsed);
+        }
+
+        Ok(bindings)
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.map.get(&action).map(|b| b.as_slice()).unwrap_or(&[])
+    }
+
+    // held actions, like movement. extra modifiers are fine so you can keep
+    // walking while holding shift
+    pub fn is_down(&self, window: &Window, action: Action) -> bool {
+        let held = Modifiers::held(window);
+
+        self.get(action)
+            .iter()
+            .any(|b| window.is_key_down(b.key) && b.modifiers.within(&held))
+    }
+
+    // one shot actions, like toggles. modifiers have to match exactly so
+    // Ctrl+P doesn't also trigger P
+    pub fn is_pressed(&self, window: &Window, action: Action) -> bool {
+        self.pressed(window, action, KeyRepeat::No)
+    }
+
+    // like is_pressed, but fires again while the key is held
+    pub fn is_repeated(&self, window: &Window, action: Action) -> bool {
+        self.pressed(window, action, KeyRepeat::Yes)
+    }
+
+    fn pressed(&self, window: &Window, action: Action, repeat: KeyRepeat) -> bool {
+        let held = Modifiers::held(window);
+
+        self.get(action)
+            .iter()
+            .any(|b| window.is_key_pressed(b.key, repeat) && b.modifiers == held)
+    }
+
+    // "W" or "H / F1", for the help overlay
+    pub fn label(&self, action: Action) -> String {
+        let labels: Vec<String> = self.get(action).iter().map(|b| b.label()).collect();
+
+        match labels.is_empty() {
+            true => String::from("unbound"),
+            false => labels.join(" / "),
+        }
+    }
+}
@@ -1,7 +1,9 @@
+pub(crate) mod bvh;
 pub(crate) mod clipping;
 pub(crate) mod data;
 pub(crate) mod matrices;
 pub(crate) mod plane;
 pub(crate) mod queue;
+pub(crate) mod ray;
 pub(crate) mod triangles;
 pub(crate) mod vectors;
@@ -0,0 +1,405 @@
+use crate::camera::frustum::Frustum;
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::matrices::multiply_matrix_vec;
+use crate::linear_algebra::ray::{intersect_triangle, Pick, Ray};
+use crate::linear_algebra::triangles::{derive_normal, Triangle};
+use crate::linear_algebra::vectors::{add_vec, dot_product, mult_vec};
+use nalgebra::base::{Matrix4, Vector4};
+
+// triangles per leaf before splitting further stops paying off
+const MAX_LEAF_SIZE: usize = 4;
+// split candidates per axis for the surface area heuristic
+const BINS: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vector4<f32>,
+    pub max: Vector4<f32>,
+}
+
+impl Aabb {
+    // grows from nothing, merging anything into it gives that thing back
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vector4::new(f32::INFINITY, f32::INFINITY, f32::INFINITY, 1.),
+            max: Vector4::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY, 1.),
+        }
+    }
+
+    pub fn from_triangle(vertices: &[Vector4<f32>; 3]) -> Self {
+        let mut aabb = Aabb::empty();
+        for v in vertices.iter() {
+            aabb.grow(v);
+        }
+
+        aabb
+    }
+
+    pub fn grow(&mut self, point: &Vector4<f32>) {
+        self.min = Vector4::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+            1.,
+        );
+        self.max = Vector4::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+            1.,
+        );
+    }
+
+    pub fn merge(&self, other: &Aabb) -> Aabb {
+        let mut merged = *self;
+        merged.grow(&other.min);
+        merged.grow(&other.max);
+
+        merged
+    }
+
+    pub fn center(&self) -> Vector4<f32> {
+        mult_vec(&add_vec(&self.min, &self.max), 0.5)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let (x, y, z) = (
+            self.max.x - self.min.x,
+            self.max.y - self.min.y,
+            self.max.z - self.min.z,
+        );
+        match x < 0. || y < 0. || z < 0. {
+            true => 0.,
+            false => 2. * (x * y + y * z + z * x),
+        }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    // distance along the ray to where it enters the box, slab test
+    pub fn ray_distance(&self, ray: &Ray) -> Option<f32> {
+        let mut near: f32 = 0.;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inv_dir = 1. / ray.dir[axis];
+            let t_1 = (self.min[axis] - ray.origin[axis]) * inv_dir;
+            let t_2 = (self.max[axis] - ray.origin[axis]) * inv_dir;
+            near = near.max(t_1.min(t_2));
+            far = far.min(t_1.max(t_2));
+        }
+
+        (near <= far).then_some(near)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    // leaf: start of its run in Bvh::indices, inner: its left child, the right one comes next
+    first: usize,
+    // triangles in a leaf, 0 for inner nodes
+    count: usize,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+// bounding volume hierarchy over one mesh, built with a binned surface area heuristic
+pub struct Bvh {
+    // root first, children always come after their parent
+    pub nodes: Vec<BvhNode>,
+    // triangle indices into Mesh::triangles, grouped by leaf
+    indices: Vec<usize>,
+    // world space triangles from the last build or refit
+    vertices: Vec<[Vector4<f32>; 3]>,
+}
+
+impl Bvh {
+    pub fn build(mesh: &Mesh, transform: &Matrix4<f32>) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: (0..mesh.triangles.len()).collect(),
+            vertices: world_vertices(mesh, transform),
+        };
+        // an empty mesh gets an empty tree, a root with no triangles would look like an inner node
+        if mesh.triangles.is_empty() {
+            return bvh;
+        }
+
+        let root = BvhNode {
+            bounds: bvh.bounds_of(0, mesh.triangles.len()),
+            first: 0,
+            count: mesh.triangles.len(),
+        };
+        bvh.nodes.push(root);
+
+        let centroids: Vec<Vector4<f32>> = bvh
+            .vertices
+            .iter()
+            .map(|vertices| Aabb::from_triangle(vertices).center())
+            .collect();
+        bvh.subdivide(0, &centroids);
+
+        bvh
+    }
+
+    // moves the boxes to follow a new transform without rebuilding the tree, cheap but
+    // the tree gets looser the further the mesh deforms from how it was built
+    pub fn refit(&mut self, mesh: &Mesh, transform: &Matrix4<f32>) {
+        self.vertices = world_vertices(mesh, transform);
+
+        for i in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[i];
+            self.nodes[i].bounds = match node.is_leaf() {
+                true => self.bounds_of(node.first, node.count),
+                false => self.nodes[node.first]
+                    .bounds
+                    .merge(&self.nodes[node.first + 1].bounds),
+            };
+        }
+    }
+
+    fn bounds_of(&self, first: usize, count: usize) -> Aabb {
+        self.indices[first..first + count]
+            .iter()
+            .fold(Aabb::empty(), |aabb, &i| {
+                aabb.merge(&Aabb::from_triangle(&self.vertices[i]))
+            })
+    }
+
+    fn subdivide(&mut self, node: usize, centroids: &[Vector4<f32>]) {
+        let (first, count) = (self.nodes[node].first, self.nodes[node].count);
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let mut centroid_bounds = Aabb::empty();
+        for &i in self.indices[first..first + count].iter() {
+            centroid_bounds.grow(&centroids[i]);
+        }
+
+        // (axis, bins left of the split, cost)
+        let mut best: Option<(usize, usize, f32)> = None;
+        let (lower, upper) = (centroid_bounds.min.xyz(), centroid_bounds.max.xyz());
+        for (axis, (&min, &max)) in lower.iter().zip(upper.iter()).enumerate() {
+            let extent = max - min;
+            if extent <= 0. {
+                continue;
+            }
+
+            let mut bins = [(Aabb::empty(), 0_usize); BINS];
+            for &i in self.indices[first..first + count].iter() {
+                let bin = bin_of(centroids[i][axis], min, extent);
+                bins[bin].0 = bins[bin].0.merge(&Aabb::from_triangle(&self.vertices[i]));
+                bins[bin].1 += 1;
+            }
+
+            for split in 1..BINS {
+                let side = |bins: &[(Aabb, usize)]| {
+                    bins.iter().fold((Aabb::empty(), 0), |(aabb, n), bin| {
+                        (aabb.merge(&bin.0), n + bin.1)
+                    })
+                };
+                let (left, left_count) = side(&bins[..split]);
+                let (right, right_count) = side(&bins[split..]);
+                let cost = left.surface_area() * left_count as f32
+                    + right.surface_area() * right_count as f32;
+
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        let leaf_cost = self.nodes[node].bounds.surface_area() * count as f32;
+        let (axis, split) = match best {
+            Some((axis, split, cost)) if cost < leaf_cost => (axis, split),
+            _ => return,
+        };
+
+        let (min, extent) = (
+            centroid_bounds.min[axis],
+            centroid_bounds.max[axis] - centroid_bounds.min[axis],
+        );
+        let (left, right): (Vec<usize>, Vec<usize>) = self.indices[first..first + count]
+            .iter()
+            .partition(|&&i| bin_of(centroids[i][axis], min, extent) < split);
+        if left.is_empty() || right.is_empty() {
+            return;
+        }
+        let left_count = left.len();
+        for (slot, i) in self.indices[first..first + count]
+            .iter_mut()
+            .zip(left.into_iter().chain(right))
+        {
+            *slot = i;
+        }
+
+        let child = self.nodes.len();
+        for (start, n) in [
+            (first, left_count),
+            (first + left_count, count - left_count),
+        ] {
+            let bounds = self.bounds_of(start, n);
+            self.nodes.push(BvhNode {
+                bounds,
+                first: start,
+                count: n,
+            });
+        }
+        self.nodes[node].first = child;
+        self.nodes[node].count = 0;
+
+        self.subdivide(child, centroids);
+        self.subdivide(child + 1, centroids);
+    }
+
+    // world space corners of a triangle, as of the last build or refit
+    pub fn triangle(&self, index: usize) -> &[Vector4<f32>; 3] {
+        &self.vertices[index]
+    }
+
+    // levels below the root, 0 for a single leaf
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        self.walk(|_, depth| {
+            deepest = deepest.max(depth);
+            true
+        });
+
+        deepest
+    }
+
+    // the nodes at `depth`, plus any leaves that end above it so the whole mesh stays covered
+    pub fn boxes_at_depth(&self, depth: usize) -> Vec<Aabb> {
+        let mut boxes: Vec<Aabb> = Vec::new();
+        self.walk(|node, node_depth| {
+            if node_depth == depth || (node.is_leaf() && node_depth < depth) {
+                boxes.push(node.bounds);
+                return false;
+            }
+            true
+        });
+
+        boxes
+    }
+
+    // closest triangle facing the ray, same rules as the brute force pick it replaces
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<Pick> {
+        let mut closest: Option<Pick> = None;
+        let mut stack: Vec<usize> = vec![0];
+
+        while let Some(i) = stack.pop() {
+            let node = match self.nodes.get(i) {
+                Some(node) => node,
+                None => continue,
+            };
+            let entry = match node.bounds.ray_distance(ray) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if closest.as_ref().is_some_and(|pick| entry > pick.distance) {
+                continue;
+            }
+
+            if !node.is_leaf() {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+
+            for &triangle in self.indices[node.first..node.first + node.count].iter() {
+                let vertices = self.vertices[triangle];
+                let normal = derive_normal(&Triangle {
+                    vertices,
+                    ..Default::default()
+                });
+                if dot_product(&normal, &ray.dir) >= 0. {
+                    continue;
+                }
+
+                let distance = match intersect_triangle(ray, &vertices) {
+                    Some(distance) => distance,
+                    None => continue,
+                };
+                if closest.as_ref().is_none_or(|pick| distance < pick.distance) {
+                    closest = Some(Pick {
+                        triangle,
+                        distance,
+                        position: ray.at(distance),
+                        normal,
+                    });
+                }
+            }
+        }
+
+        closest
+    }
+
+    // triangles whose bounding boxes overlap `aabb`
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        self.collect(|bounds| bounds.overlaps(aabb))
+    }
+
+    // triangles whose bounding boxes are at least partly inside the frustum
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<usize> {
+        self.collect(|bounds| frustum.intersects_box(&bounds.min, &bounds.max))
+    }
+
+    // every triangle reached through nodes, and with its own box, passing `test`
+    fn collect(&self, test: impl Fn(&Aabb) -> bool) -> Vec<usize> {
+        let mut triangles: Vec<usize> = Vec::new();
+        self.walk(|node, _| {
+            if !test(&node.bounds) {
+                return false;
+            }
+            if node.is_leaf() {
+                triangles.extend(
+                    self.indices[node.first..node.first + node.count]
+                        .iter()
+                        .filter(|&&i| test(&Aabb::from_triangle(&self.vertices[i]))),
+                );
+            }
+            true
+        });
+
+        triangles
+    }
+
+    // depth first, `visit` returns whether to go into the node's children
+    fn walk(&self, mut visit: impl FnMut(&BvhNode, usize) -> bool) {
+        let mut stack: Vec<(usize, usize)> = match self.nodes.is_empty() {
+            true => vec![],
+            false => vec![(0, 0)],
+        };
+
+        while let Some((i, depth)) = stack.pop() {
+            let node = &self.nodes[i];
+            if visit(node, depth) && !node.is_leaf() {
+                stack.push((node.first + 1, depth + 1));
+                stack.push((node.first, depth + 1));
+            }
+        }
+    }
+}
+
+fn world_vertices(mesh: &Mesh, transform: &Matrix4<f32>) -> Vec<[Vector4<f32>; 3]> {
+    mesh.triangles
+        .iter()
+        .map(|triangle| {
+            triangle
+                .vertices
+                .map(|v| multiply_matrix_vec(transform, &v))
+        })
+        .collect()
+}
+
+fn bin_of(value: f32, min: f32, extent: f32) -> usize {
+    (((value - min) / extent * BINS as f32) as usize).min(BINS - 1)
+}
@@ -4,6 +4,14 @@ use crate::{HEIGHT, WIDTH};
 
 use super::{plane::triangle_clip_plane, triangles::Triangle};
 
+// any corner past the edges clip_against_window cuts along
+pub fn off_screen(triangle: &Triangle) -> bool {
+    triangle
+        .vertices
+        .iter()
+        .any(|v| v.x < 0. || v.y < 0. || v.x > WIDTH as f32 - 1. || v.y > HEIGHT as f32 - 1.)
+}
+
 pub fn clip_against_window(
     clipped: &mut [Triangle; 2],
     vec_of_triangles: &mut Vec<Triangle>,
@@ -1,6 +1,29 @@
+use crate::linear_algebra::matrices::multiply_matrix_vec;
 use crate::linear_algebra::triangles::Triangle;
+use nalgebra::base::{Matrix4, Vector4};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mesh {
     pub triangles: Vec<Triangle>,
 }
+
+impl Mesh {
+    // axis aligned min and max corners after `transform`, None for an empty mesh
+    pub fn bounds(&self, transform: &Matrix4<f32>) -> Option<(Vector4<f32>, Vector4<f32>)> {
+        let mut vertices = self
+            .triangles
+            .iter()
+            .flat_map(|tri| tri.vertices.iter())
+            .map(|v| multiply_matrix_vec(transform, v));
+
+        let first = vertices.next()?;
+        let bounds = vertices.fold((first, first), |(min, max), v| {
+            (
+                Vector4::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z), 1.),
+                Vector4::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z), 1.),
+            )
+        });
+
+        Some(bounds)
+    }
+}
@@ -87,6 +87,22 @@ pub fn multiply_matrix_vec(mat: &Matrix4<f32>, input_vec: &Vector4<f32>) -> Vect
     )
 }
 
+// direction normal to a surface -> the same surface's normal after `mat`, by the
+// inverse transpose so it stays perpendicular under scaling. translation is ignored
+pub fn transform_normal(mat: &Matrix4<f32>, normal: &Vector4<f32>) -> Vector4<f32> {
+    let linear = mat.fixed_view::<3, 3>(0, 0).into_owned();
+    let inverse_transpose = linear
+        .try_inverse()
+        .map_or(linear, |inverse| inverse.transpose());
+    let column = |c: usize| {
+        normal.x * inverse_transpose[(0, c)]
+            + normal.y * inverse_transpose[(1, c)]
+            + normal.z * inverse_transpose[(2, c)]
+    };
+
+    normalize_vec(&Vector4::new(column(0), column(1), column(2), 1.))
+}
+
 pub fn multiply_matrices(m1: &Matrix4<f32>, m2: &Matrix4<f32>) -> Matrix4<f32> {
     let mut multiplied: Matrix4<f32> = Matrix4::zeros();
 
@@ -171,3 +187,17 @@ pub fn world_matrix(
         &create_trans_matrix(trans_vec.x, trans_vec.y, trans_vec.z),
     )
 }
+
+// spins the mesh by theta around z and x, then moves it out to trans_vec
+pub fn create_world_matrix(theta: &f32, trans_vec: &Vector4<f32>) -> Matrix4<f32> {
+    world_matrix(
+        trans_vec,
+        &create_z_rot_mat(theta),
+        &create_x_rot_mat(theta),
+    )
+}
+
+// full inverse, invert_matrix only handles rotation + translation so it can't undo a projection
+pub fn invert_projection_matrix(mat: &Matrix4<f32>) -> Matrix4<f32> {
+    mat.try_inverse().unwrap_or_else(Matrix4::identity)
+}
@@ -1,9 +1,11 @@
 use nalgebra::Vector4;
+use raqote::SolidSource;
 
 use super::{
     triangles::Triangle,
     vectors::{add_vec, dot_product, mult_vec, normalize_vec, sub_vec},
 };
+use crate::drawing::colors::mix_colors;
 
 pub fn triangle_clip_plane(
     pl_point: &Vector4<f32>,
@@ -24,14 +26,19 @@ pub fn triangle_clip_plane(
     let mut outside_count: usize = 0;
     let mut inside_points: Vec<Vector4<f32>> = Vec::new();
     let mut inside_count: usize = 0;
+    // corner colors ride along so new corners can blend between their ends
+    let mut outside_colors: Vec<SolidSource> = Vec::new();
+    let mut inside_colors: Vec<SolidSource> = Vec::new();
 
     // if distance is greater than 0, it's inside; if less, it's outside
     for (i, element) in distances.iter().enumerate() {
         if *element >= 0. {
             inside_points.push(in_tri.vertices[i]);
+            inside_colors.push(corner_color(in_tri, i));
             inside_count += 1;
         } else {
             outside_points.push(in_tri.vertices[i]);
+            outside_colors.push(corner_color(in_tri, i));
             outside_count += 1;
         }
     }
@@ -61,6 +68,15 @@ pub fn triangle_clip_plane(
         *out_tri_1.vertices[2] =
             *vec_intersect_plane(&pl_point, &pl_normal, &inside_points[0], &outside_points[1]);
 
+        if in_tri.vertex_colors.is_some() {
+            let blend = |o: usize| {
+                let t =
+                    intersect_fraction(pl_point, pl_normal, &inside_points[0], &outside_points[o]);
+                mix_colors(inside_colors[0], outside_colors[o], t)
+            };
+            out_tri_1.vertex_colors = Some([inside_colors[0], blend(0), blend(1)]);
+        }
+
         return 1; // Return the newly formed single triangle
     } else {
         *out_tri_1 = in_tri.clone();
@@ -81,6 +97,17 @@ pub fn triangle_clip_plane(
         *out_tri_2.vertices[1] = *out_tri_1.vertices[2];
         *out_tri_2.vertices[2] =
             *vec_intersect_plane(&pl_point, &pl_normal, &inside_points[1], &outside_points[0]);
+
+        if in_tri.vertex_colors.is_some() {
+            let blend = |i: usize| {
+                let t =
+                    intersect_fraction(pl_point, pl_normal, &inside_points[i], &outside_points[0]);
+                mix_colors(inside_colors[i], outside_colors[0], t)
+            };
+            let (first, second) = (blend(0), blend(1));
+            out_tri_1.vertex_colors = Some([inside_colors[0], inside_colors[1], first]);
+            out_tri_2.vertex_colors = Some([inside_colors[1], first, second]);
+        }
         return 2;
     }
 }
@@ -100,13 +127,30 @@ pub fn vec_intersect_plane(
     line_s: &Vector4<f32>,
     line_e: &Vector4<f32>,
 ) -> Vector4<f32> {
+    let t: f32 = intersect_fraction(pl_point, pl_normal, line_s, line_e);
+    let full_line: Vector4<f32> = sub_vec(&line_e, &line_s);
+    let line_intersect = mult_vec(&full_line, t);
+    return add_vec(&line_s, &line_intersect);
+}
+
+// how far along line_s -> line_e the plane is crossed, 0 at the start and 1 at the end
+fn intersect_fraction(
+    pl_point: &Vector4<f32>,
+    pl_normal: &Vector4<f32>,
+    line_s: &Vector4<f32>,
+    line_e: &Vector4<f32>,
+) -> f32 {
     let normal_plane: Vector4<f32> = normalize_vec(pl_normal);
 
     let plane_dot: f32 = dot_product(&normal_plane, &pl_point);
     let ad = dot_product(&line_s, &normal_plane);
     let bd = dot_product(&line_e, &normal_plane);
-    let t: f32 = (plane_dot - ad) / (bd - ad);
-    let full_line: Vector4<f32> = sub_vec(&line_e, &line_s);
-    let line_intersect = mult_vec(&full_line, t);
-    return add_vec(&line_s, &line_intersect);
+    (plane_dot - ad) / (bd - ad)
+}
+
+fn corner_color(triangle: &Triangle, i: usize) -> SolidSource {
+    match triangle.vertex_colors {
+        Some(colors) => colors[i],
+        None => triangle.color,
+    }
 }
@@ -1,55 +1,86 @@
+use crate::camera::frustum::Frustum;
 use crate::camera::lightsource::add_lightsource;
+use crate::camera::view::Camera;
+use crate::drawing::fog::FogSettings;
+use crate::drawing::stats::FrameStats;
+use crate::linear_algebra::bvh::{Aabb, Bvh};
+use crate::linear_algebra::clipping::off_screen;
 use crate::linear_algebra::data::Mesh;
-use crate::linear_algebra::matrices::{
-    create_point_at_matrix, create_y_rot_mat, invert_matrix, multiply_matrix_vec, world_matrix,
-};
-use crate::linear_algebra::matrices::{create_x_rot_mat, create_z_rot_mat};
+use crate::linear_algebra::matrices::{multiply_matrix_vec, transform_normal};
 use crate::linear_algebra::plane::triangle_clip_plane;
+use crate::linear_algebra::ray::Pick;
 use crate::linear_algebra::triangles::{derive_normal, Triangle};
 use crate::linear_algebra::vectors::dot_product;
-use crate::linear_algebra::vectors::{add_vec, scale_x_y, sub_vec};
+use crate::linear_algebra::vectors::{
+    add_vec, mult_vec, normalize_vec, position_key, scale_point, scale_x_y, sub_vec,
+};
 use nalgebra::base::{Matrix4, Vector4};
+use std::collections::{HashMap, HashSet};
+
+// screen space line segments for the normals debug view
+pub struct NormalLines {
+    pub faces: Vec<[Vector4<f32>; 2]>,
+    pub vertices: Vec<[Vector4<f32>; 2]>,
+}
+
+// screen space outlines of a picked triangle and the bounding box of its mesh
+pub struct SelectionLines {
+    pub triangle: Vec<[Vector4<f32>; 2]>,
+    pub bounds: Vec<[Vector4<f32>; 2]>,
+}
 
 pub fn get_triangle_queue(
-    theta: &f32,
-    trans_vec: &Vector4<f32>,
-    yaw: &f32,
-    look_dir: &mut Vector4<f32>,
-    camera: &Vector4<f32>,
-    mesh: &mut Mesh,
+    world_matrix: &Matrix4<f32>,
+    camera: &Camera,
+    mesh: &Mesh,
     projection_matrix: Matrix4<f32>,
+    fog: &FogSettings,
+    bvh: Option<&Bvh>,
+    stats: &mut FrameStats,
 ) -> Vec<Triangle> {
-    // increment rotation angle & rotation matrices
-    let x_rot_matrix: Matrix4<f32> = create_x_rot_mat(theta);
-    let z_rot_matrix: Matrix4<f32> = create_z_rot_mat(theta);
-    let world_matrix: Matrix4<f32> = world_matrix(&trans_vec, &z_rot_matrix, &x_rot_matrix);
-
     // camera stuff
-    let up: Vector4<f32> = Vector4::new(0., 1., 0., 1.);
-    let mut target: Vector4<f32> = Vector4::new(0., 0., 1., 1.);
-
-    let rotated_cam: Matrix4<f32> = create_y_rot_mat(&yaw);
-    *look_dir = multiply_matrix_vec(&rotated_cam, &target);
-    target = add_vec(&camera, &look_dir);
-    let point: Matrix4<f32> = create_point_at_matrix(*camera, target, up);
-    let view: Matrix4<f32> = invert_matrix(&point);
+    let view: Matrix4<f32> = camera.view_matrix();
+    let frustum = Frustum::from_camera(camera, &projection_matrix);
 
     // get a queue to later order
     let mut triangle_queue: Vec<Triangle> = Vec::new();
 
-    for triangle in mesh.triangles.iter_mut() {
-        let mut trans_triangle: Triangle = Triangle { ..*triangle };
+    stats.submitted += mesh.triangles.len();
+
+    // a tree fitted to this mesh and world matrix skips whole branches out of view,
+    // without one every triangle is tested on its own
+    let candidates: Vec<usize> = match bvh {
+        Some(bvh) => bvh.query_frustum(&frustum),
+        None => (0..mesh.triangles.len()).collect(),
+    };
+    stats.culled += mesh.triangles.len() - candidates.len();
+
+    for &index in candidates.iter() {
+        let mut trans_triangle: Triangle = Triangle {
+            ..mesh.triangles[index]
+        };
 
         // world matrix
         for i in 0..3 {
             trans_triangle.vertices[i] =
-                multiply_matrix_vec(&world_matrix, &trans_triangle.vertices[i])
+                multiply_matrix_vec(world_matrix, &trans_triangle.vertices[i])
+        }
+        if let Some(normals) = trans_triangle.normals.as_mut() {
+            *normals = normals.map(|n| transform_normal(world_matrix, &n));
+        }
+
+        if bvh.is_none() {
+            let bounds = Aabb::from_triangle(&trans_triangle.vertices);
+            if !frustum.intersects_box(&bounds.min, &bounds.max) {
+                stats.culled += 1;
+                continue;
+            }
         }
 
         //normals
         let normal: Vector4<f32> = derive_normal(&trans_triangle);
 
-        let camera_ray: Vector4<f32> = sub_vec(&trans_triangle.vertices[0], &camera);
+        let camera_ray: Vector4<f32> = sub_vec(&trans_triangle.vertices[0], &camera.position);
 
         if dot_product(&normal, &camera_ray) < 0. {
             // add light
@@ -61,6 +92,14 @@ pub fn get_triangle_queue(
                     multiply_matrix_vec(&view, &trans_triangle.vertices[i]);
             }
 
+            // fade toward the fog color by view space depth
+            trans_triangle.color = fog.apply(trans_triangle.color, trans_triangle.get_avg_z());
+            if let Some(colors) = trans_triangle.vertex_colors.as_mut() {
+                for (color, vertex) in colors.iter_mut().zip(trans_triangle.vertices.iter()) {
+                    *color = fog.apply(*color, vertex.z);
+                }
+            }
+
             // Clip Viewed Triangle against near plane, this could form two additional
             // additional triangles.
             let mut clipped: [Triangle; 2] = [Triangle::default(), Triangle::default()];
@@ -70,6 +109,10 @@ pub fn get_triangle_queue(
                 &trans_triangle,
                 &mut clipped,
             );
+            // counted once however many planes cut it, render cuts along the window
+            // edges later but leaves the counting to here
+            let mut was_clipped =
+                num_clipped_triangles != 1 || trans_triangle.vertices.iter().any(|v| v.z < 1.);
 
             for n in 0..num_clipped_triangles {
                 let mut clip_tri: Triangle = clipped[n as usize].clone();
@@ -81,10 +124,16 @@ pub fn get_triangle_queue(
 
                 // Scale into view
                 scale_x_y(&mut clip_tri);
+                was_clipped |= off_screen(&clip_tri);
 
                 // add to final triangle queue
                 triangle_queue.push(clip_tri);
             }
+            if was_clipped {
+                stats.clipped += 1;
+            }
+        } else {
+            stats.culled += 1;
         }
     }
 
@@ -93,3 +142,177 @@ pub fn get_triangle_queue(
 
     triangle_queue
 }
+
+pub fn get_normal_lines(
+    world_matrix: &Matrix4<f32>,
+    camera: &Camera,
+    mesh: &Mesh,
+    projection_matrix: Matrix4<f32>,
+    length: f32,
+) -> NormalLines {
+    let view: Matrix4<f32> = camera.view_matrix();
+
+    let world_triangles: Vec<[Vector4<f32>; 3]> = mesh
+        .triangles
+        .iter()
+        .map(|tri| tri.vertices.map(|v| multiply_matrix_vec(world_matrix, &v)))
+        .collect();
+
+    // vertex normals are the mesh's own where it has them, otherwise the average
+    // of every face touching that position
+    let mut smooth: HashMap<[u32; 3], Vector4<f32>> = HashMap::new();
+    for vertices in world_triangles.iter() {
+        let normal = derive_normal(&Triangle {
+            vertices: *vertices,
+            ..Default::default()
+        });
+        for v in vertices.iter() {
+            let sum = smooth
+                .entry(position_key(v))
+                .or_insert(Vector4::new(0., 0., 0., 1.));
+            *sum = add_vec(sum, &normal);
+        }
+    }
+
+    let mut lines = NormalLines {
+        faces: Vec::new(),
+        vertices: Vec::new(),
+    };
+    // a position can have a few normals where a crease runs through it
+    let mut drawn: HashSet<([u32; 3], [u32; 3])> = HashSet::new();
+
+    for (vertices, triangle) in world_triangles.iter().zip(mesh.triangles.iter()) {
+        let normal = derive_normal(&Triangle {
+            vertices: *vertices,
+            ..Default::default()
+        });
+        let camera_ray: Vector4<f32> = sub_vec(&vertices[0], &camera.position);
+        if dot_product(&normal, &camera_ray) >= 0. {
+            continue;
+        }
+
+        let centroid = mult_vec(
+            &add_vec(&add_vec(&vertices[0], &vertices[1]), &vertices[2]),
+            1. / 3.,
+        );
+        let tip = add_vec(&centroid, &mult_vec(&normal, length));
+        if let Some(line) = project_line(&centroid, &tip, &view, &projection_matrix) {
+            lines.faces.push(line);
+        }
+
+        for (i, v) in vertices.iter().enumerate() {
+            let normal = match triangle.normals {
+                Some(normals) => transform_normal(world_matrix, &normals[i]),
+                None => normalize_vec(&smooth[&position_key(v)]),
+            };
+            if !drawn.insert((position_key(v), position_key(&normal))) {
+                continue;
+            }
+            let tip = add_vec(v, &mult_vec(&normal, length));
+            if let Some(line) = project_line(v, &tip, &view, &projection_matrix) {
+                lines.vertices.push(line);
+            }
+        }
+    }
+
+    lines
+}
+
+pub fn get_selection_lines(
+    world_matrix: &Matrix4<f32>,
+    camera: &Camera,
+    mesh: &Mesh,
+    projection_matrix: Matrix4<f32>,
+    pick: &Pick,
+) -> SelectionLines {
+    let view: Matrix4<f32> = camera.view_matrix();
+
+    let mut lines = SelectionLines {
+        triangle: Vec::new(),
+        bounds: Vec::new(),
+    };
+
+    // follows the triangle as the mesh moves, rather than where it was when picked
+    if let Some(triangle) = mesh.triangles.get(pick.triangle) {
+        let vertices = triangle
+            .vertices
+            .map(|v| multiply_matrix_vec(world_matrix, &v));
+        for i in 0..3 {
+            let (start, end) = (&vertices[i], &vertices[(i + 1) % 3]);
+            if let Some(line) = project_line(start, end, &view, &projection_matrix) {
+                lines.triangle.push(line);
+            }
+        }
+    }
+
+    if let Some((min, max)) = mesh.bounds(world_matrix) {
+        push_box_lines(&min, &max, &view, &projection_matrix, &mut lines.bounds);
+    }
+
+    lines
+}
+
+// screen space edges of world space boxes, for the bvh debug view
+pub fn get_box_lines(
+    boxes: &[Aabb],
+    camera: &Camera,
+    projection_matrix: Matrix4<f32>,
+) -> Vec<[Vector4<f32>; 2]> {
+    let view: Matrix4<f32> = camera.view_matrix();
+    let mut lines: Vec<[Vector4<f32>; 2]> = Vec::new();
+
+    for aabb in boxes.iter() {
+        push_box_lines(&aabb.min, &aabb.max, &view, &projection_matrix, &mut lines);
+    }
+
+    lines
+}
+
+fn push_box_lines(
+    min: &Vector4<f32>,
+    max: &Vector4<f32>,
+    view: &Matrix4<f32>,
+    projection_matrix: &Matrix4<f32>,
+    lines: &mut Vec<[Vector4<f32>; 2]>,
+) {
+    let corner = |i: usize| {
+        Vector4::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+            1.,
+        )
+    };
+
+    // every pair of corners that differ along exactly one axis
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            if i & axis == 0 {
+                if let Some(line) =
+                    project_line(&corner(i), &corner(i | axis), view, projection_matrix)
+                {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+}
+
+// world space segment -> screen space, dropped if either end is behind the near plane
+fn project_line(
+    start: &Vector4<f32>,
+    end: &Vector4<f32>,
+    view: &Matrix4<f32>,
+    projection_matrix: &Matrix4<f32>,
+) -> Option<[Vector4<f32>; 2]> {
+    let start = multiply_matrix_vec(view, start);
+    let end = multiply_matrix_vec(view, end);
+    if start.z < 1. || end.z < 1. {
+        return None;
+    }
+
+    Some([
+        scale_point(&multiply_matrix_vec(projection_matrix, &start)),
+        scale_point(&multiply_matrix_vec(projection_matrix, &end)),
+    ])
+}
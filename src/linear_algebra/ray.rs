@@ -0,0 +1,151 @@
+use crate::camera::view::Camera;
+use crate::linear_algebra::matrices::{
+    invert_matrix, invert_projection_matrix, multiply_matrix_vec,
+};
+use crate::linear_algebra::vectors::{
+    add_vec, cross_product, div_vec, dot_product, mult_vec, normalize_vec, sub_vec, unscale_point,
+};
+use nalgebra::base::{Matrix4, Vector4};
+
+#[derive(Debug, Clone)]
+pub struct Ray {
+    pub origin: Vector4<f32>,
+    // unit length
+    pub dir: Vector4<f32>,
+}
+
+// what a ray hit, in world space
+#[derive(Debug, Clone)]
+pub struct Pick {
+    // index into Mesh::triangles
+    pub triangle: usize,
+    pub distance: f32,
+    pub position: Vector4<f32>,
+    pub normal: Vector4<f32>,
+}
+
+impl Ray {
+    // ray through a point on screen, undoing scale_point, the projection and the view
+    pub fn from_screen(x: f32, y: f32, camera: &Camera, projection_matrix: &Matrix4<f32>) -> Ray {
+        let (ndc_x, ndc_y) = unscale_point(x, y);
+
+        let inverse_projection = invert_projection_matrix(projection_matrix);
+        let camera_matrix = invert_matrix(&camera.view_matrix());
+
+        // the same pixel on the near plane and halfway into the depth range
+        let unproject = |depth: f32| {
+            let view =
+                multiply_matrix_vec(&inverse_projection, &Vector4::new(ndc_x, ndc_y, depth, 1.));
+            multiply_matrix_vec(&camera_matrix, &div_vec(&view, view.w))
+        };
+        let near = unproject(0.);
+        let far = unproject(0.5);
+
+        Ray {
+            origin: near,
+            dir: normalize_vec(&sub_vec(&far, &near)),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vector4<f32> {
+        add_vec(&self.origin, &mult_vec(&self.dir, distance))
+    }
+}
+
+// Möller–Trumbore, distance along the ray to where it crosses the triangle
+pub fn intersect_triangle(ray: &Ray, vertices: &[Vector4<f32>; 3]) -> Option<f32> {
+    let edge_1 = sub_vec(&vertices[1], &vertices[0]);
+    let edge_2 = sub_vec(&vertices[2], &vertices[0]);
+
+    let p = cross_product(&ray.dir, &edge_2);
+    let det = dot_product(&edge_1, &p);
+    // parallel to the triangle
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1. / det;
+
+    let s = sub_vec(&ray.origin, &vertices[0]);
+    let u = dot_product(&s, &p) * inv_det;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+
+    let q = cross_product(&s, &edge_1);
+    let v = dot_product(&ray.dir, &q) * inv_det;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+
+    let distance = dot_product(&edge_2, &q) * inv_det;
+    (distance > f32::EPSILON).then_some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linear_algebra::matrices::create_projection_matrix;
+    use crate::linear_algebra::vectors::scale_point;
+
+    // where get_triangle_queue would draw a world space point
+    fn project(
+        point: &Vector4<f32>,
+        camera: &Camera,
+        projection_matrix: &Matrix4<f32>,
+    ) -> Vector4<f32> {
+        let view = multiply_matrix_vec(&camera.view_matrix(), point);
+        scale_point(&multiply_matrix_vec(projection_matrix, &view))
+    }
+
+    #[test]
+    fn unscale_point_undoes_scale_point() {
+        let projected = Vector4::new(0.3, -0.7, 0.5, 2.);
+        let screen = scale_point(&projected);
+        let (x, y) = unscale_point(screen.x, screen.y);
+
+        assert!((x - 0.15).abs() < 1e-5 && (y + 0.35).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_through_a_projected_vertex_picks_it() {
+        let projection_matrix = create_projection_matrix();
+        let cameras = [
+            Camera::default(),
+            Camera {
+                position: Vector4::new(1., -2., -3., 1.),
+                yaw: 20.,
+                pitch: -15.,
+                roll: 10.,
+            },
+        ];
+        let vertices = [
+            Vector4::new(-1., -0.5, 6., 1.),
+            Vector4::new(1.5, -0.2, 7., 1.),
+            Vector4::new(0.2, 1.3, 6.5, 1.),
+        ];
+        let middle = mult_vec(
+            &add_vec(&add_vec(&vertices[0], &vertices[1]), &vertices[2]),
+            1. / 3.,
+        );
+
+        for camera in cameras.iter() {
+            let screen = project(&middle, camera, &projection_matrix);
+            let ray = Ray::from_screen(screen.x, screen.y, camera, &projection_matrix);
+
+            let distance = intersect_triangle(&ray, &vertices)
+                .or_else(|| intersect_triangle(&ray, &[vertices[0], vertices[2], vertices[1]]))
+                .expect("ray missed the triangle under it");
+            let hit = ray.at(distance);
+            assert!(
+                vec_distance(&hit, &middle) < 1e-3,
+                "hit {:?}, wanted {:?}",
+                hit,
+                middle
+            );
+        }
+    }
+
+    fn vec_distance(a: &Vector4<f32>, b: &Vector4<f32>) -> f32 {
+        crate::linear_algebra::vectors::vec_magnitude(&sub_vec(a, b))
+    }
+}
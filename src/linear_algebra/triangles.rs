@@ -1,4 +1,6 @@
-use super::vectors::{cross_product, get_line, normalize_vec};
+use super::vectors::{
+    add_vec, cross_product, dot_product, get_line, mult_vec, normalize_vec, sub_vec,
+};
 use nalgebra::base::Vector4;
 use raqote::SolidSource;
 
@@ -6,6 +8,21 @@ use raqote::SolidSource;
 pub struct Triangle {
     pub vertices: [Vector4<f32>; 3],
     pub color: SolidSource,
+    // unlit color for meshes that bring their own, shaded by the light into `color`
+    pub base_color: Option<SolidSource>,
+    // per corner colors from meshes that have them, unlit in the mesh and
+    // lit in the queue, the rasterizer blends them across the face
+    pub vertex_colors: Option<[SolidSource; 3]>,
+    // per corner texture coordinates from the file, nothing samples them yet
+    // but they're kept so exports don't lose them
+    pub uvs: Option<[[f32; 2]; 3]>,
+    // per corner normals from Mesh::recompute_normals, each corner is lit on its
+    // own and blended across the face. None lights the face flat by its winding
+    pub normals: Option<[Vector4<f32>; 3]>,
+    // carries on the polygon the triangle before it started, sharing its first
+    // corner and the edge it ended on. set where an obj or ply polygon was split
+    // into a fan, so subdivision can put it back together
+    pub fan: bool,
 }
 
 impl Triangle {
@@ -19,6 +36,11 @@ impl Clone for Triangle {
         Triangle {
             vertices: self.vertices,
             color: self.color,
+            base_color: self.base_color,
+            vertex_colors: self.vertex_colors,
+            uvs: self.uvs,
+            normals: self.normals,
+            fan: self.fan,
         }
     }
 }
@@ -36,6 +58,11 @@ impl Default for Triangle {
                 b: 0x67,
                 a: 0xff,
             },
+            base_color: None,
+            vertex_colors: None,
+            uvs: None,
+            normals: None,
+            fan: false,
         }
     }
 }
@@ -47,3 +74,60 @@ pub fn derive_normal(triangle: &Triangle) -> Vector4<f32> {
 
     normalize_vec(&cross_product(&line_1, &line_2))
 }
+
+// point on the triangle nearest to `point`, from Ericson's Real-Time Collision Detection
+pub fn closest_point_on_triangle(
+    point: &Vector4<f32>,
+    vertices: &[Vector4<f32>; 3],
+) -> Vector4<f32> {
+    let [a, b, c] = vertices;
+    let ab = sub_vec(b, a);
+    let ac = sub_vec(c, a);
+
+    // vertex region outside a
+    let ap = sub_vec(point, a);
+    let (d1, d2) = (dot_product(&ab, &ap), dot_product(&ac, &ap));
+    if d1 <= 0. && d2 <= 0. {
+        return *a;
+    }
+
+    // vertex region outside b
+    let bp = sub_vec(point, b);
+    let (d3, d4) = (dot_product(&ab, &bp), dot_product(&ac, &bp));
+    if d3 >= 0. && d4 <= d3 {
+        return *b;
+    }
+
+    // edge region of ab
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0. && d1 >= 0. && d3 <= 0. {
+        return add_vec(a, &mult_vec(&ab, d1 / (d1 - d3)));
+    }
+
+    // vertex region outside c
+    let cp = sub_vec(point, c);
+    let (d5, d6) = (dot_product(&ab, &cp), dot_product(&ac, &cp));
+    if d6 >= 0. && d5 <= d6 {
+        return *c;
+    }
+
+    // edge region of ac
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0. && d2 >= 0. && d6 <= 0. {
+        return add_vec(a, &mult_vec(&ac, d2 / (d2 - d6)));
+    }
+
+    // edge region of bc
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0. && (d4 - d3) >= 0. && (d5 - d6) >= 0. {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return add_vec(b, &mult_vec(&sub_vec(c, b), w));
+    }
+
+    // inside the face
+    let denom = 1. / (va + vb + vc);
+    add_vec(
+        a,
+        &add_vec(&mult_vec(&ab, vb * denom), &mult_vec(&ac, vc * denom)),
+    )
+}
@@ -2,25 +2,40 @@ use crate::{Triangle, HEIGHT, WIDTH};
 use nalgebra::base::Vector4;
 
 pub fn scale_x_y(triangle: &mut Triangle) {
+    for i in 0..3 {
+        triangle.vertices[i] = scale_point(&triangle.vertices[i]);
+    }
+}
+
+// projected point -> screen space
+pub fn scale_point(point: &Vector4<f32>) -> Vector4<f32> {
     let offset_view: Vector4<f32> = Vector4::new(1., 1., 0., 0.);
 
     // scale into cartesian
-    for i in 0..3 {
-        triangle.vertices[i] = div_vec(&triangle.vertices[i], triangle.vertices[i].w)
-    }
+    let mut scaled: Vector4<f32> = div_vec(point, point.w);
 
     // invert x and y
-    for i in 0..3 {
-        triangle.vertices[i].x *= -1.;
-        triangle.vertices[i].y *= -1.;
-    }
+    scaled.x *= -1.;
+    scaled.y *= -1.;
 
     // offset into normal space and invert x and y
-    for i in 0..3 {
-        triangle.vertices[i] = add_vec(&triangle.vertices[i], &offset_view);
-        triangle.vertices[i].x *= (triangle.vertices[i].y + 1.) * 0.5 * WIDTH as f32;
-        triangle.vertices[i].y *= (triangle.vertices[i].y + 1.) * 0.5 * HEIGHT as f32;
-    }
+    scaled = add_vec(&scaled, &offset_view);
+    scaled.x *= 0.5 * WIDTH as f32;
+    scaled.y *= 0.5 * HEIGHT as f32;
+
+    scaled
+}
+
+// screen space -> projected x and y, the inverse of scale_point. anything turning
+// pixels back into directions goes through here so they all agree with the raster
+pub fn unscale_point(x: f32, y: f32) -> (f32, f32) {
+    (1. - 2. * x / WIDTH as f32, 1. - 2. * y / HEIGHT as f32)
+}
+
+// exact position as something hashable, -0 stays apart from 0 so nothing is
+// merged by accident. everywhere corners are matched by position goes through here
+pub fn position_key(v: &Vector4<f32>) -> [u32; 3] {
+    [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]
 }
 
 pub fn get_line(vec1: &Vector4<f32>, vec2: &Vector4<f32>) -> Vector4<f32> {
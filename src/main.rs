@@ -1,26 +1,65 @@
-use crate::drawing::render::render;
+use crate::camera::view::Camera;
+use crate::drawing::background::{draw_background, Background, Gradient, Skybox};
+use crate::drawing::capture::{save_comparison, save_mesh_snapshot, Recorder};
+use crate::drawing::fog::FogSettings;
+use crate::drawing::overlay::{draw_help, draw_hud, help_pages, Notice};
+use crate::drawing::render::{present, render, render_boxes, render_normals, render_selection};
+use crate::drawing::settings::{RenderMode, RenderSettings};
+use crate::drawing::stats::FrameStats;
+use crate::drawing::tracer::{trace_image, Scene, TraceSettings};
+use crate::linear_algebra::bvh::Bvh;
 use crate::linear_algebra::data::Mesh;
-use crate::linear_algebra::matrices::create_projection_matrix;
+use crate::linear_algebra::matrices::{
+    create_projection_matrix, create_world_matrix, multiply_matrix_vec,
+};
+use crate::linear_algebra::ray::Pick;
 use crate::linear_algebra::triangles::Triangle;
-use crate::meshes::initialize_mesh::get_mesh;
+use crate::linear_algebra::vectors::{mult_vec, sub_vec};
+use crate::meshes::initialize_mesh::Model;
+use crate::meshes::load_error::LoadError;
+use crate::meshes::lod::LodChain;
+use crate::meshes::noise::NoiseSettings;
+use crate::meshes::terrain::{
+    get_terrain_from_heightmap, get_terrain_from_noise, TerrainColoring, TerrainSettings,
+};
+use crate::meshes::watch::MeshWatcher;
 
-use drawing::controls::initialize_user_controls;
-use linear_algebra::queue::get_triangle_queue;
+use camera::walk::Walker;
+use drawing::controls::{
+    initialize_capture_controls, initialize_clock_controls, initialize_user_controls,
+    pick_under_cursor, walk_controls, CameraControls, CameraMode,
+};
+use linear_algebra::queue::{
+    get_box_lines, get_normal_lines, get_selection_lines, get_triangle_queue,
+};
 use meshes::cube::_get_cube_mesh;
 // use meshes::cube::get_cube_mesh;
-use minifb::{Key, Window, WindowOptions};
+use input::actions::Action;
+use input::args::{Args, Subject, USAGE};
+use input::bindings::Bindings;
+use minifb::{Window, WindowOptions};
 use nalgebra::base::{Matrix4, Vector4};
 use raqote::DrawTarget;
+use timing::clock::Clock;
 
 mod camera;
 mod drawing;
+mod input;
 mod linear_algebra;
 mod meshes;
+mod timing;
 
 pub const WIDTH: usize = 300;
 pub const HEIGHT: usize = 300;
+// triangles the subdivision toggle stops short of
+const MAX_SUBDIVIDED: usize = 100_000;
 
 fn main() {
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        std::process::exit(2);
+    });
+
     let mut window = Window::new(
         "Baby Graphics Engine - ESC to exit",
         WIDTH,
@@ -35,43 +74,473 @@ fn main() {
     window.limit_update_rate(Some(std::time::Duration::from_micros(16660)));
 
     // setting up mesh
-    // let mut mesh: Mesh = get_mesh("src/meshes/meshes/video_ship.obj");
-    // let mut mesh: Mesh = get_mesh("src/meshes/meshes/teapot.obj");
-    let mut mesh = _get_cube_mesh();
+    // childs_play src/meshes/meshes/teapot.obj
+    // childs_play part.stl --weld 1e-4
+    // childs_play scene.glb
+    // a model given on the command line is watched while the viewer runs, saving it
+    // or its mtl libraries and textures swaps the new version in.
+    // `--terrain [seed]` generates hills instead, `--heightmap <png>` raises them
+    // from an image, either shaded by slope rather than height with `--slope`.
+    // terrain keeps its own size, it's made to be walked on.
+    // `--skybox <dir>` loads a cube map from px.png, nx.png, .. nz.png in dir.
+    // `--weld <eps>` joins a model's corners closer than eps once it's sized, 1e-4
+    // is plenty for stl. `--decimate <n>` simplifies a model to n triangles.
+    // see Args::parse for how they combine
+    let terrain = TerrainSettings {
+        coloring: match args.slope {
+            true => TerrainColoring::Slope,
+            false => TerrainColoring::Elevation,
+        },
+        ..Default::default()
+    };
+    let mut watcher: Option<MeshWatcher> = None;
+    // cameras a gltf file placed, prepared along with its mesh
+    let mut file_cameras: Vec<Camera> = Vec::new();
+    let mut on_terrain = false;
+    let mut mesh = match &args.subject {
+        Subject::Cube => _get_cube_mesh(),
+        Subject::Noise(seed) => {
+            let noise = NoiseSettings {
+                seed: seed.unwrap_or(NoiseSettings::default().seed),
+                ..Default::default()
+            };
+            on_terrain = true;
+            get_terrain_from_noise(&terrain, &noise)
+        }
+        Subject::Heightmap(file_path) => match get_terrain_from_heightmap(file_path, &terrain) {
+            Ok(mesh) => {
+                on_terrain = true;
+                mesh
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                _get_cube_mesh()
+            }
+        },
+        Subject::Model(file_path) => {
+            let (Model { mut mesh, cameras }, watching) = MeshWatcher::load(file_path);
+            watcher = Some(watching);
+            file_cameras = cameras;
+            prepare_mesh(&mut mesh, &mut file_cameras, &args);
+            mesh
+        }
+    };
+
+    // cycle_background steps through these, starting on the skybox when there is one
+    let mut backgrounds: Vec<Background> = vec![
+        Background::default(),
+        Background::Gradient(Gradient::default()),
+    ];
+    let mut background_index = 0;
+    if let Some(directory) = &args.skybox {
+        match Skybox::load_dir(directory) {
+            Ok(skybox) => {
+                backgrounds.push(Background::Skybox(skybox));
+                background_index = backgrounds.len() - 1;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    // fog goes with the scene, the mountains want something like
+    // FogSettings { mode: FogMode::Linear, start: 20., end: 150., ..Default::default() }.
+    // cycle_fog steps through the modes, whichever is on fades into the background
+    let mut fog = FogSettings::default().with_background(&backgrounds[background_index]);
+
+    // reference renderer, F9 saves it next to the rasterized frame
+    let trace_settings = TraceSettings::default();
 
     // setting up other globals
     let projection_matrix: Matrix4<f32> = create_projection_matrix();
     let trans_vec: Vector4<f32> = Vector4::new(0., 0., 8., 1.);
-    let mut yaw = 0.;
     let mut theta: f32 = 0.;
-    let speed = 0.1;
-    let mut camera: Vector4<f32> = Vector4::new(0., 0., 0., 1.);
-    let mut look_dir = Vector4::new(0., 0., 1., 1.);
+    // world units per second
+    let speed = 6.;
+    // degrees per second
+    let spin_speed = 60.;
+    // faces meeting at a sharper angle than this stay creased when smoothed
+    let crease_angle = 60.;
+    let mut smooth_normals = false;
+    // smoothing passes for coarse models, and the mesh from before them while on
+    let subdivision_levels = 2;
+    let mut coarse: Option<Mesh> = None;
+    let mut clock: Clock = Clock::default();
+    let mut controls: CameraControls = CameraControls::default();
+    let mut walker: Walker = Walker::default();
+    let mut turntable = true;
+    // which of file_cameras is being looked through, the first from the start.
+    // they only line up with the model unspun, so the turntable stays off
+    let mut file_camera: Option<usize> = None;
+    if let Some(camera) = file_cameras.first() {
+        controls.camera = place_file_camera(camera, &trans_vec);
+        turntable = false;
+        file_camera = Some(0);
+    }
+    // terrain is for walking over rather than spinning, so it starts still with the
+    // camera above its near edge looking across it
+    if on_terrain {
+        turntable = false;
+        controls.camera.position = Vector4::new(
+            0.,
+            terrain.height_scale + 2.,
+            trans_vec.z - terrain.size * 0.5,
+            1.,
+        );
+        controls.camera.pitch = -25.;
+    }
+    // page of the help overlay, None while it's hidden
+    let mut help_page: Option<usize> = None;
+    let mut show_hud = true;
+    // what the last action did, when there's more to say than the hud shows
+    let mut notice: Notice = Notice::default();
+    let mut stats: FrameStats = FrameStats::default();
+    let mut recorder: Recorder = Recorder::default();
+    let mut render_settings: RenderSettings = RenderSettings::default();
+    let mut selection: Option<Pick> = None;
+    let mut bvh: Bvh = Bvh::build(&mesh, &create_world_matrix(&theta, &trans_vec));
+    // coarser copies drawn when the mesh is small on screen. picking, tracing and
+    // exporting always use the full mesh
+    let mut lods: LodChain = LodChain::default();
+    lods.rebuild(&mesh);
+
+    // rebinding is optional, without a bindings file the defaults are used
+    let bindings: Bindings = match Bindings::load("bindings.cfg") {
+        Ok(bindings) => bindings,
+        Err(LoadError::Io(_)) => Bindings::default(),
+        Err(e) => {
+            eprintln!("{}, using default bindings", e);
+            Bindings::default()
+        }
+    };
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
+    while window.is_open() && !bindings.is_down(&window, Action::Quit) {
         let mut dt = DrawTarget::new(WIDTH as i32, HEIGHT as i32);
 
-        theta += 1.;
+        clock.tick();
+        stats.begin_frame(clock.frame_time);
+        initialize_clock_controls(&window, &mut clock, &bindings);
+
+        // reloads were parsed in the background, swapping one in only rebuilds the
+        // tree. one that failed keeps the old mesh and says why on the hud
+        if let Some(reloaded) = watcher.as_mut().and_then(MeshWatcher::poll) {
+            (mesh, file_cameras) = (reloaded.mesh, reloaded.cameras);
+            prepare_mesh(&mut mesh, &mut file_cameras, &args);
+            file_camera = None;
+            if coarse.is_some() {
+                coarse = Some(mesh.clone());
+                subdivide_within(&mut mesh, subdivision_levels, crease_angle);
+            }
+            if smooth_normals {
+                mesh.recompute_normals(crease_angle);
+            }
+            bvh = Bvh::build(&mesh, &create_world_matrix(&theta, &trans_vec));
+            lods.rebuild(&mesh);
+            selection = None;
+        }
+
+        if bindings.is_pressed(&window, Action::ToggleHud) {
+            show_hud = !show_hud;
+        }
+        // every page in turn, then hidden again
+        if bindings.is_pressed(&window, Action::ToggleHelp) {
+            help_page = match help_page {
+                None => Some(0),
+                Some(page) if page + 1 < help_pages() => Some(page + 1),
+                Some(_) => None,
+            };
+        }
+
+        if bindings.is_pressed(&window, Action::CycleRenderMode) {
+            render_settings.cycle_mode();
+        }
+        if bindings.is_pressed(&window, Action::ToggleWireframe) {
+            render_settings.toggle_wireframe();
+        }
+        if bindings.is_pressed(&window, Action::CycleAntiAliasing) {
+            render_settings.anti_aliasing = render_settings.anti_aliasing.next();
+        }
+        if bindings.is_pressed(&window, Action::ToggleFxaa) {
+            render_settings.fxaa = !render_settings.fxaa;
+        }
+        if bindings.is_pressed(&window, Action::CycleFog) {
+            fog.mode = fog.mode.next();
+        }
+        if bindings.is_pressed(&window, Action::CycleBackground) {
+            background_index = (background_index + 1) % backgrounds.len();
+            fog = fog.with_background(&backgrounds[background_index]);
+        }
+        // off, then every level from the root down
+        if bindings.is_pressed(&window, Action::CycleBvhDepth) {
+            render_settings.bvh_depth = match render_settings.bvh_depth {
+                None => Some(0),
+                Some(depth) if depth < bvh.depth() => Some(depth + 1),
+                Some(_) => None,
+            };
+        }
+        if bindings.is_pressed(&window, Action::ToggleSmoothNormals) {
+            smooth_normals = !smooth_normals;
+            let angle = if smooth_normals { crease_angle } else { 0. };
+            mesh.recompute_normals(angle);
+            lods.apply(|level| level.recompute_normals(angle));
+        }
+        // for assets with inverted faces the culling hides
+        if bindings.is_pressed(&window, Action::UnifyWinding) {
+            notice.show(format!("flipped {} faces", mesh.unify_winding()));
+            if let Some(coarse) = coarse.as_mut() {
+                coarse.unify_winding();
+            }
+            bvh = Bvh::build(&mesh, &create_world_matrix(&theta, &trans_vec));
+            lods.apply(|level| {
+                level.unify_winding();
+            });
+        }
+        if bindings.is_pressed(&window, Action::FlipWinding) {
+            mesh.flip_winding();
+            if let Some(coarse) = coarse.as_mut() {
+                coarse.flip_winding();
+            }
+            bvh = Bvh::build(&mesh, &create_world_matrix(&theta, &trans_vec));
+            lods.apply(Mesh::flip_winding);
+        }
+        if bindings.is_pressed(&window, Action::CycleLod) {
+            lods.cycle();
+        }
+        // creases follow the same angle as the normals
+        if bindings.is_pressed(&window, Action::ToggleSubdivision) {
+            match coarse.take() {
+                Some(original) => mesh = original,
+                None => {
+                    coarse = Some(mesh.clone());
+                    subdivide_within(&mut mesh, subdivision_levels, crease_angle);
+                }
+            }
+            mesh.recompute_normals(if smooth_normals { crease_angle } else { 0. });
+            bvh = Bvh::build(&mesh, &create_world_matrix(&theta, &trans_vec));
+            lods.rebuild(&mesh);
+            selection = None;
+        }
+
+        // toggles the turntable spin
+        if bindings.is_pressed(&window, Action::ToggleTurntable) {
+            turntable = !turntable;
+        }
+        if turntable {
+            theta += spin_speed * clock.scaled_delta;
+        }
+
+        // steps through the file's cameras, winding the turntable back to match
+        if bindings.is_pressed(&window, Action::CycleFileCamera) && !file_cameras.is_empty() {
+            let next = file_camera.map_or(0, |i| (i + 1) % file_cameras.len());
+            controls.camera = place_file_camera(&file_cameras[next], &trans_vec);
+            controls.mode = CameraMode::Fly;
+            turntable = false;
+            theta = 0.;
+            bvh.refit(&mesh, &create_world_matrix(&theta, &trans_vec));
+            file_camera = Some(next);
+        }
+
+        // frames the whole mesh in the orbit camera
+        if bindings.is_pressed(&window, Action::FrameMesh) {
+            if let Some((min, max)) = mesh.bounds(&create_world_matrix(&theta, &trans_vec)) {
+                controls.orbit.frame(&min, &max);
+                controls.mode = CameraMode::Orbit;
+            }
+        }
+
+        // the tree follows the mesh as it spins, it's rebuilt whenever the mesh changes
+        let picking = bindings.is_pressed(&window, Action::Pick);
+        let tracing = bindings.is_pressed(&window, Action::RayTrace);
+        if turntable && clock.scaled_delta != 0. {
+            bvh.refit(&mesh, &create_world_matrix(&theta, &trans_vec));
+        }
+
+        // walking stops the turntable and stands the mesh back up, the ground
+        // shouldn't spin or tilt underfoot
+        if bindings.is_pressed(&window, Action::ToggleWalkMode) {
+            controls.mode = match controls.mode {
+                CameraMode::Walk => CameraMode::Fly,
+                _ => {
+                    turntable = false;
+                    theta = 0.;
+                    bvh.refit(&mesh, &create_world_matrix(&theta, &trans_vec));
+                    walker.start(&mut controls.camera);
+                    CameraMode::Walk
+                }
+            };
+        }
+        // theta is settled for the frame, everything drawn from here on shares this
+        let world_matrix = create_world_matrix(&theta, &trans_vec);
+
+        initialize_user_controls(&mut window, &mut controls, &bindings, &speed, &clock.delta);
+        if controls.mode == CameraMode::Walk {
+            walk_controls(
+                &mut window,
+                &mut controls.camera,
+                &mut walker,
+                &mut controls.mouse,
+                &bindings,
+                &bvh,
+                &clock.delta,
+            );
+        }
+
+        if picking {
+            selection = pick_under_cursor(
+                &window,
+                &controls.camera,
+                &controls.mouse,
+                &bvh,
+                &projection_matrix,
+            );
+        }
 
-        initialize_user_controls(
-            &window,
+        draw_background(
             &mut dt,
-            &mut camera,
-            &mut look_dir,
-            &mut yaw,
-            &speed,
+            &backgrounds[background_index],
+            &controls.camera,
+            &projection_matrix,
         );
 
+        lods.poll(&mesh);
+        let level = lods.select(&world_matrix, &controls.camera, &projection_matrix);
+        stats.lod = level;
+        stats.lod_levels = lods.level_count();
+        stats.lod_fixed = lods.fixed.is_some();
+        let drawn = lods.mesh(level, &mesh);
+
+        // the tree only knows the full mesh, coarser levels are culled triangle by triangle
         let triangle_queue = get_triangle_queue(
-            &theta,
-            &trans_vec,
-            &yaw,
-            &mut look_dir,
-            &camera,
-            &mut mesh,
+            &world_matrix,
+            &controls.camera,
+            drawn,
             projection_matrix,
+            &fog,
+            (level == 0).then_some(&bvh),
+            &mut stats,
         );
         // render
-        render(&mut window, triangle_queue, &mut dt)
+        render(triangle_queue, &mut dt, &render_settings, &mut stats);
+
+        for (action, extension) in [
+            (Action::ExportMesh, "obj"),
+            (Action::ExportStl, "stl"),
+            (Action::ExportPly, "ply"),
+        ] {
+            if !bindings.is_pressed(&window, action) {
+                continue;
+            }
+            match save_mesh_snapshot(&mesh, &world_matrix, &recorder.directory, extension) {
+                Ok(path) => println!("saved mesh to {}", path.display()),
+                Err(e) => eprintln!("couldn't save mesh: {}", e),
+            }
+        }
+
+        // traced against the frame just drawn, before any overlays go on top
+        if tracing {
+            let scene = Scene {
+                mesh: &mesh,
+                world_matrix: &world_matrix,
+                bvh: &bvh,
+                camera: &controls.camera,
+                projection_matrix: &projection_matrix,
+                background: &backgrounds[background_index],
+                fog: &fog,
+            };
+            let traced = trace_image(&scene, &trace_settings);
+            match save_comparison(&dt, &traced, &recorder.directory) {
+                Ok(path) => println!("saved ray traced comparison to {}", path.display()),
+                Err(e) => eprintln!("couldn't save ray traced image: {}", e),
+            }
+        }
+
+        if render_settings.mode == RenderMode::Normals {
+            let lines = get_normal_lines(
+                &world_matrix,
+                &controls.camera,
+                drawn,
+                projection_matrix,
+                render_settings.normal_length,
+            );
+            render_normals(&lines, &mut dt, &render_settings);
+        }
+
+        if let Some(depth) = render_settings.bvh_depth {
+            let lines = get_box_lines(
+                &bvh.boxes_at_depth(depth),
+                &controls.camera,
+                projection_matrix,
+            );
+            render_boxes(&lines, &mut dt, &render_settings);
+        }
+
+        if let Some(pick) = &selection {
+            let lines = get_selection_lines(
+                &world_matrix,
+                &controls.camera,
+                &mesh,
+                projection_matrix,
+                pick,
+            );
+            render_selection(&lines, &mut dt, &render_settings);
+        }
+
+        if show_hud {
+            draw_hud(
+                &mut dt,
+                &stats,
+                &controls.camera,
+                &render_settings,
+                &fog,
+                selection.as_ref(),
+                notice
+                    .text()
+                    .or(watcher.as_ref().and_then(MeshWatcher::notice)),
+            );
+        }
+        if let Some(page) = help_page {
+            draw_help(&mut dt, &bindings, page);
+        }
+
+        initialize_capture_controls(&window, &dt, &mut recorder, &mut clock, &bindings);
+
+        present(&mut window, &dt);
     }
 }
+
+// models come in at any size and place. centered and this big they fill about
+// half the view at trans_vec's distance, whatever they were modelled in. with
+// `--weld <eps>`, corners closer than eps at that size are joined, stl writes
+// every facet's own and exporters round them differently. `--decimate <n>` then
+// collapses it down to n triangles. cameras from the file are moved with it, so
+// they keep looking at the same part of the model
+fn prepare_mesh(mesh: &mut Mesh, cameras: &mut [Camera], args: &Args) {
+    let middle = mesh.center();
+    let scale = mesh.scale_to_size(5.);
+    for camera in cameras.iter_mut() {
+        camera.position = mult_vec(&sub_vec(&camera.position, &middle), scale);
+    }
+    if let Some(tolerance) = args.weld {
+        mesh.weld(tolerance);
+    }
+    mesh.remove_degenerate();
+    if let Some(target) = args.decimate {
+        mesh.decimate(target, f32::INFINITY);
+    }
+}
+
+// a prepared file camera, moved out to where the model sits before it spins
+fn place_file_camera(camera: &Camera, trans_vec: &Vector4<f32>) -> Camera {
+    Camera {
+        position: multiply_matrix_vec(&create_world_matrix(&0., trans_vec), &camera.position),
+        ..camera.clone()
+    }
+}
+
+// dense models get fewer levels or none, see Mesh::subdivided_len for the growth
+fn subdivide_within(mesh: &mut Mesh, levels: usize, crease_angle: f32) {
+    let levels = (0..=levels)
+        .rev()
+        .find(|&level| mesh.subdivided_len(level) <= MAX_SUBDIVIDED)
+        .unwrap_or(0);
+    mesh.subdivide(levels, crease_angle);
+}
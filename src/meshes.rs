@@ -1,2 +1,16 @@
+pub(crate) mod archive;
+pub(crate) mod cache;
 pub(crate) mod cube;
+pub(crate) mod export;
+pub(crate) mod gltf;
 pub(crate) mod initialize_mesh;
+pub(crate) mod load_error;
+pub(crate) mod lod;
+pub(crate) mod noise;
+pub(crate) mod ply;
+pub(crate) mod process;
+pub(crate) mod simplify;
+pub(crate) mod stl;
+pub(crate) mod subdivide;
+pub(crate) mod terrain;
+pub(crate) mod watch;
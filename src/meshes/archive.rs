@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::Read;
+
+use zip::ZipArchive;
+
+use crate::meshes::gltf::import_gltf_slice;
+use crate::meshes::initialize_mesh::{extension, parse_model, report_warnings, Model};
+use crate::meshes::load_error::LoadError;
+
+// entries load_from_archive can open
+const MODEL_EXTENSIONS: [&str; 5] = ["obj", "stl", "ply", "gltf", "glb"];
+
+// "archive.zip#inner/path.obj" -> ("archive.zip", Some("inner/path.obj")),
+// "archive.zip" -> ("archive.zip", None), None for anything that isn't a zip
+pub fn split_archive_path(file_path: &str) -> Option<(&str, Option<&str>)> {
+    let (archive, inner) = match file_path.split_once('#') {
+        Some((archive, inner)) => (archive, Some(inner)),
+        None => (file_path, None),
+    };
+
+    match extension(archive).as_deref() {
+        Some("zip") => Some((archive, inner)),
+        _ => None,
+    }
+}
+
+// every model in the archive, by its path inside it
+pub fn list_models(archive_path: &str) -> Result<Vec<String>, LoadError> {
+    let archive = ZipArchive::new(File::open(archive_path)?)?;
+
+    Ok(models(&archive))
+}
+
+// reads straight out of the archive, nothing is extracted to disk. without `inner`
+// the archive has to hold exactly one model. an obj's mtl libraries and textures
+// are looked up next to it inside the archive
+pub fn load_from_archive(archive_path: &str, inner: Option<&str>) -> Result<Model, LoadError> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+
+    let inner = match inner {
+        Some(inner) => inner.to_string(),
+        None => match models(&archive).as_slice() {
+            [only] => only.clone(),
+            [] => {
+                return Err(LoadError::Parse(format!(
+                    "{}: no models inside",
+                    archive_path
+                )))
+            }
+            several => {
+                return Err(LoadError::Parse(format!(
+                    "{}: holds several models, pick one with {}#<model>: {}",
+                    archive_path,
+                    archive_path,
+                    several.join(", ")
+                )))
+            }
+        },
+    };
+
+    let label = format!("{}#{}", archive_path, inner);
+    let bytes = read_entry(&mut archive, &inner)?;
+
+    match extension(&inner).as_deref() {
+        Some("gltf") | Some("glb") => {
+            Ok(report_warnings(import_gltf_slice(&bytes, &label)?, &label))
+        }
+        _ => {
+            let directory = match inner.rsplit_once('/') {
+                Some((directory, _)) => format!("{}/", directory),
+                None => String::new(),
+            };
+
+            let mesh = parse_model(&bytes, &label, &mut |name| {
+                read_entry(&mut archive, &join(&directory, name)).ok()
+            })?;
+
+            Ok(mesh.into())
+        }
+    }
+}
+
+fn models(archive: &ZipArchive<File>) -> Vec<String> {
+    let mut models: Vec<String> = archive
+        .file_names()
+        .filter(|name| {
+            extension(name).is_some_and(|extension| MODEL_EXTENSIONS.contains(&extension.as_str()))
+        })
+        .map(String::from)
+        .collect();
+    models.sort();
+
+    models
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, LoadError> {
+    let mut entry = archive.by_name(name)?;
+    let mut bytes: Vec<u8> = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+// zip paths always use /, and a side file can climb out of the model's folder with ..
+fn join(directory: &str, name: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in directory.split('/').chain(name.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
@@ -0,0 +1,357 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nalgebra::Vector4;
+use raqote::SolidSource;
+
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::Triangle;
+use crate::meshes::initialize_mesh::parse_model;
+use crate::meshes::load_error::LoadError;
+
+const MAGIC: &[u8; 4] = b"MSHC";
+// bump whenever the layout below or what the parsers read changes, older caches
+// are then ignored and rewritten
+const VERSION: u32 = 4;
+
+// which of a triangle's optional fields follow its vertices
+const BASE_COLOR: u8 = 1;
+const VERTEX_COLORS: u8 = 2;
+const UVS: u8 = 4;
+const NORMALS: u8 = 8;
+// Triangle::fan, nothing follows for this one
+const FAN: u8 = 16;
+
+// all little endian, read back with one fs::read:
+//   magic, version u32, source length u64, source hash u64
+//   side file count u32, each: name length u32, name, present u8, hash u64
+//   triangle count u32, each: flags u8, 9 f32 positions,
+//     then rgba base color, 3 rgba vertex colors, 6 f32 uvs and 9 f32 normals,
+//     as flagged
+// the cache is used only when the source and every side file it read
+// (an obj's mtl libraries and textures) still hash the same
+
+// file_path's mesh, from file_path.meshcache when that is still current, otherwise
+// parsed and the cache written for next time. the side files it was built from,
+// found or not, are added to `sources`
+pub fn load_cached(file_path: &str, sources: &mut Vec<PathBuf>) -> Result<Mesh, LoadError> {
+    let source = fs::read(file_path)?;
+    let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let cache_path = format!("{}.meshcache", file_path);
+
+    if let Some(mesh) = fs::read(&cache_path)
+        .ok()
+        .and_then(|cache| read_cache(&cache, &source, directory, sources))
+    {
+        return Ok(mesh);
+    }
+
+    let mut side_files: Vec<(String, Option<u64>)> = Vec::new();
+    let mesh = parse_model(&source, file_path, &mut |name| {
+        let bytes = fs::read(directory.join(name)).ok();
+        side_files.push((name.to_string(), bytes.as_deref().map(hash)));
+        bytes
+    })?;
+    sources.extend(side_files.iter().map(|(name, _)| directory.join(name)));
+
+    // written aside and renamed so a reader never sees half a cache. one that can't
+    // be written, say in a read only folder, just means parsing again next time
+    let partial = format!("{}.partial", cache_path);
+    if fs::write(&partial, write_cache(&mesh, &source, &side_files)).is_ok() {
+        let _ = fs::rename(&partial, &cache_path);
+    }
+
+    Ok(mesh)
+}
+
+// None for a stale, foreign or damaged cache
+fn read_cache(
+    cache: &[u8],
+    source: &[u8],
+    directory: &Path,
+    sources: &mut Vec<PathBuf>,
+) -> Option<Mesh> {
+    let mut reader = Reader {
+        bytes: cache,
+        at: 0,
+    };
+
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+        return None;
+    }
+    if reader.u64()? != source.len() as u64 || reader.u64()? != hash(source) {
+        return None;
+    }
+
+    let mut side_files: Vec<PathBuf> = Vec::new();
+    for _ in 0..reader.u32()? {
+        let length = reader.u32()? as usize;
+        let name = String::from_utf8(reader.take(length)?.to_vec()).ok()?;
+        let recorded = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.u64()?),
+        };
+        let side_file = directory.join(&name);
+        if fs::read(&side_file).ok().as_deref().map(hash) != recorded {
+            return None;
+        }
+        side_files.push(side_file);
+    }
+
+    let count = reader.u32()? as usize;
+    // every triangle takes at least its flags and positions
+    if count > (cache.len() - reader.at) / 37 {
+        return None;
+    }
+    let mut triangles: Vec<Triangle> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let flags = reader.u8()?;
+        let mut triangle = Triangle::default();
+        for vertex in triangle.vertices.iter_mut() {
+            *vertex = Vector4::new(reader.f32()?, reader.f32()?, reader.f32()?, 1.);
+        }
+        if flags & BASE_COLOR != 0 {
+            triangle.base_color = Some(reader.color()?);
+        }
+        if flags & VERTEX_COLORS != 0 {
+            triangle.vertex_colors = Some([reader.color()?, reader.color()?, reader.color()?]);
+        }
+        if flags & UVS != 0 {
+            let mut uvs: [[f32; 2]; 3] = [[0.; 2]; 3];
+            for uv in uvs.iter_mut() {
+                *uv = [reader.f32()?, reader.f32()?];
+            }
+            triangle.uvs = Some(uvs);
+        }
+        if flags & NORMALS != 0 {
+            let mut normals: [Vector4<f32>; 3] = triangle.vertices;
+            for normal in normals.iter_mut() {
+                *normal = Vector4::new(reader.f32()?, reader.f32()?, reader.f32()?, 1.);
+            }
+            triangle.normals = Some(normals);
+        }
+        triangle.fan = flags & FAN != 0;
+        triangles.push(triangle);
+    }
+
+    if reader.at != cache.len() {
+        return None;
+    }
+    sources.extend(side_files);
+
+    Some(Mesh { triangles })
+}
+
+fn write_cache(mesh: &Mesh, source: &[u8], side_files: &[(String, Option<u64>)]) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(64 + mesh.triangles.len() * 37);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(source.len() as u64).to_le_bytes());
+    out.extend_from_slice(&hash(source).to_le_bytes());
+
+    out.extend_from_slice(&(side_files.len() as u32).to_le_bytes());
+    for (name, recorded) in side_files.iter() {
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        match recorded {
+            Some(recorded) => {
+                out.push(1);
+                out.extend_from_slice(&recorded.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+    }
+
+    let color = |out: &mut Vec<u8>, c: &SolidSource| out.extend_from_slice(&[c.r, c.g, c.b, c.a]);
+    out.extend_from_slice(&(mesh.triangles.len() as u32).to_le_bytes());
+    for triangle in mesh.triangles.iter() {
+        let flags = [
+            (triangle.base_color.is_some(), BASE_COLOR),
+            (triangle.vertex_colors.is_some(), VERTEX_COLORS),
+            (triangle.uvs.is_some(), UVS),
+            (triangle.normals.is_some(), NORMALS),
+            (triangle.fan, FAN),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag);
+        out.push(flags);
+
+        for vertex in triangle.vertices.iter() {
+            for value in [vertex.x, vertex.y, vertex.z] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if let Some(base_color) = triangle.base_color.as_ref() {
+            color(&mut out, base_color);
+        }
+        if let Some(vertex_colors) = triangle.vertex_colors.as_ref() {
+            for vertex_color in vertex_colors.iter() {
+                color(&mut out, vertex_color);
+            }
+        }
+        if let Some(uvs) = triangle.uvs.as_ref() {
+            for value in uvs.iter().flatten() {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if let Some(normals) = triangle.normals.as_ref() {
+            for normal in normals.iter() {
+                for value in [normal.x, normal.y, normal.z] {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    out
+}
+
+// fnv-1a, std's hasher isn't promised to stay the same between rust versions
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// walks the cache, None once it runs out
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Option<&[u8]> {
+        let taken = self.bytes.get(self.at..self.at.checked_add(count)?)?;
+        self.at += count;
+        Some(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.array()?))
+    }
+
+    fn color(&mut self) -> Option<SolidSource> {
+        let [r, g, b, a] = self.array()?;
+        Some(SolidSource { r, g, b, a })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a folder of its own under the temp dir, emptied first
+    fn scratch(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("meshcache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn colors(mesh: &Mesh) -> Vec<Option<SolidSource>> {
+        mesh.triangles
+            .iter()
+            .map(|triangle| triangle.base_color)
+            .collect()
+    }
+
+    #[test]
+    fn a_cache_reads_back_what_was_parsed() {
+        let directory = scratch("round-trip");
+        let obj = directory.join("quad.obj");
+        fs::write(
+            &obj,
+            "mtllib quad.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\n\
+             usemtl red\nf 1/1 2/1 3/2 4/2\n",
+        )
+        .unwrap();
+        fs::write(directory.join("quad.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        let obj = obj.to_str().unwrap();
+
+        let mut sources: Vec<PathBuf> = Vec::new();
+        let parsed = load_cached(obj, &mut sources).unwrap();
+        assert_eq!(sources, [directory.join("quad.mtl")]);
+
+        let cache = fs::read(format!("{}.meshcache", obj)).unwrap();
+        let mut sources: Vec<PathBuf> = Vec::new();
+        let cached = read_cache(&cache, &fs::read(obj).unwrap(), &directory, &mut sources).unwrap();
+        assert_eq!(sources, [directory.join("quad.mtl")]);
+
+        assert_eq!(cached.triangles.len(), 2);
+        for (cached, parsed) in cached.triangles.iter().zip(parsed.triangles.iter()) {
+            assert_eq!(cached.vertices, parsed.vertices);
+            assert_eq!(cached.base_color, parsed.base_color);
+            assert_eq!(cached.uvs, parsed.uvs);
+            assert_eq!(cached.fan, parsed.fan);
+        }
+        assert!(cached.triangles[1].fan);
+
+        // a damaged cache is ignored rather than misread
+        assert!(read_cache(
+            &cache[..cache.len() - 1],
+            &fs::read(obj).unwrap(),
+            &directory,
+            &mut sources
+        )
+        .is_none());
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn changing_a_side_file_invalidates_the_cache() {
+        let directory = scratch("side-file");
+        let obj = directory.join("tri.obj");
+        fs::write(
+            &obj,
+            "mtllib tri.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl paint\nf 1 2 3\n",
+        )
+        .unwrap();
+        let mtl = directory.join("tri.mtl");
+        fs::write(&mtl, "newmtl paint\nKd 1 0 0\n").unwrap();
+        let obj = obj.to_str().unwrap();
+        let red = SolidSource::from_unpremultiplied_argb(0xff, 255, 0, 0);
+        let blue = SolidSource::from_unpremultiplied_argb(0xff, 0, 0, 255);
+
+        assert_eq!(
+            colors(&load_cached(obj, &mut Vec::new()).unwrap()),
+            [Some(red)]
+        );
+        assert_eq!(
+            colors(&load_cached(obj, &mut Vec::new()).unwrap()),
+            [Some(red)]
+        );
+
+        fs::write(&mtl, "newmtl paint\nKd 0 0 1\n").unwrap();
+        let cache = fs::read(format!("{}.meshcache", obj)).unwrap();
+        assert!(read_cache(&cache, &fs::read(obj).unwrap(), &directory, &mut Vec::new()).is_none());
+        assert_eq!(
+            colors(&load_cached(obj, &mut Vec::new()).unwrap()),
+            [Some(blue)]
+        );
+
+        // and so does a side file going missing
+        fs::remove_file(&mtl).unwrap();
+        assert_eq!(colors(&load_cached(obj, &mut Vec::new()).unwrap()), [None]);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use nalgebra::base::{Matrix4, Vector4};
+use raqote::SolidSource;
+
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::matrices::{multiply_matrix_vec, transform_normal};
+use crate::linear_algebra::triangles::{derive_normal, Triangle};
+use crate::linear_algebra::vectors::position_key;
+
+// picks a writer by file extension, like load_mesh_tracked. `transform` bakes a world
+// matrix into the written positions, None writes the mesh as it is stored
+pub fn save_mesh(mesh: &Mesh, transform: Option<&Matrix4<f32>>, file_path: &str) -> io::Result<()> {
+    let extension = Path::new(file_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+
+    match extension.as_deref() {
+        Some("obj") => save_obj(mesh, transform, file_path),
+        Some("stl") => save_stl(mesh, transform, file_path),
+        Some("ply") => save_ply(mesh, transform, file_path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: can only save obj, stl and ply", file_path),
+        )),
+    }
+}
+
+// positions, uvs and normals, shared where they're bit for bit equal. faces without
+// corner normals get their face normal.
+// base colors go into a .mtl next to it as one Kd material per color
+pub fn save_obj(mesh: &Mesh, transform: Option<&Matrix4<f32>>, file_path: &str) -> io::Result<()> {
+    let triangles = baked(mesh, transform);
+    let path = Path::new(file_path);
+    let library = path.with_extension("mtl");
+
+    let mut positions: Indexed<[u32; 3]> = Indexed::default();
+    let mut uvs: Indexed<[u32; 2]> = Indexed::default();
+    let mut normals: Indexed<[u32; 3]> = Indexed::default();
+    let mut materials: Indexed<Option<[u8; 3]>> = Indexed::default();
+
+    // obj indices start at 1
+    let mut faces: Vec<(usize, [String; 3])> = Vec::with_capacity(triangles.len());
+    for triangle in triangles.iter() {
+        // the triangle's corner normals, or its face normal at every corner
+        let face = derive_normal(triangle);
+        let corner_normals = match (triangle.normals, face.x.is_finite()) {
+            (Some(corners), _) => corners.map(Some),
+            (None, true) => [Some(face); 3],
+            (None, false) => [None; 3],
+        };
+        let material = materials.index(triangle.base_color.map(|c| [c.r, c.g, c.b]));
+
+        let corners = [0, 1, 2].map(|i| {
+            let vertex = positions.index(position_key(&triangle.vertices[i])) + 1;
+            let normal = corner_normals[i].map(|normal| normals.index(position_key(&normal)) + 1);
+            let uv = triangle
+                .uvs
+                .map(|uvs| uvs[i].map(f32::to_bits))
+                .map(|uv| uvs.index(uv) + 1);
+
+            match (uv, normal) {
+                (Some(uv), Some(normal)) => format!("{}/{}/{}", vertex, uv, normal),
+                (Some(uv), None) => format!("{}/{}", vertex, uv),
+                (None, Some(normal)) => format!("{}//{}", vertex, normal),
+                (None, None) => format!("{}", vertex),
+            }
+        });
+        faces.push((material, corners));
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "# {} triangles", triangles.len())?;
+    if let Some(name) = library.file_name() {
+        writeln!(out, "mtllib {}", name.to_string_lossy())?;
+    }
+    for [x, y, z] in positions.items.iter() {
+        writeln!(out, "v {} {} {}", float(*x), float(*y), float(*z))?;
+    }
+    for [u, v] in uvs.items.iter() {
+        writeln!(out, "vt {} {}", float(*u), float(*v))?;
+    }
+    for [x, y, z] in normals.items.iter() {
+        writeln!(out, "vn {} {} {}", float(*x), float(*y), float(*z))?;
+    }
+
+    let mut current: Option<usize> = None;
+    for (material, corners) in faces.iter() {
+        if current != Some(*material) {
+            writeln!(out, "usemtl {}", material_name(&materials.items[*material]))?;
+            current = Some(*material);
+        }
+        writeln!(out, "f {} {} {}", corners[0], corners[1], corners[2])?;
+    }
+    out.flush()?;
+
+    // uncolored faces get a material without a Kd, so they stay uncolored when read back
+    let mut out = BufWriter::new(File::create(&library)?);
+    for color in materials.items.iter() {
+        writeln!(out, "newmtl {}", material_name(color))?;
+        if let Some([r, g, b]) = color {
+            let channel = |value: u8| value as f32 / 255.;
+            writeln!(out, "Kd {} {} {}", channel(*r), channel(*g), channel(*b))?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+// binary, the facet normals are derived from the winding so parse_stl keeps it
+pub fn save_stl(mesh: &Mesh, transform: Option<&Matrix4<f32>>, file_path: &str) -> io::Result<()> {
+    let triangles = baked(mesh, transform);
+    let mut out = BufWriter::new(File::create(file_path)?);
+
+    let mut header: [u8; 80] = [0; 80];
+    let name = b"binary stl";
+    header[..name.len()].copy_from_slice(name);
+    out.write_all(&header)?;
+    out.write_all(&(triangles.len() as u32).to_le_bytes())?;
+
+    for triangle in triangles.iter() {
+        let normal = derive_normal(triangle);
+        let normal = match normal.x.is_finite() {
+            true => normal,
+            false => Vector4::new(0., 0., 0., 1.),
+        };
+        for point in std::iter::once(&normal).chain(triangle.vertices.iter()) {
+            for value in [point.x, point.y, point.z] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+        // attribute byte count, unused
+        out.write_all(&[0, 0])?;
+    }
+    out.flush()
+}
+
+// binary little endian. when any face is colored every vertex gets a color, from
+// vertex_colors or else the face's base color, so flat colored faces keep theirs
+pub fn save_ply(mesh: &Mesh, transform: Option<&Matrix4<f32>>, file_path: &str) -> io::Result<()> {
+    let triangles = baked(mesh, transform);
+    let colored = triangles
+        .iter()
+        .any(|t| t.vertex_colors.is_some() || t.base_color.is_some());
+
+    // vertices are shared where both position and color match
+    let mut vertices: Indexed<([u32; 3], [u8; 3])> = Indexed::default();
+    let mut faces: Vec<[usize; 3]> = Vec::with_capacity(triangles.len());
+    for triangle in triangles.iter() {
+        let colors = corner_colors(triangle);
+        faces.push([0, 1, 2].map(|i| {
+            let color = match colored {
+                true => [colors[i].r, colors[i].g, colors[i].b],
+                false => [0; 3],
+            };
+            vertices.index((position_key(&triangle.vertices[i]), color))
+        }));
+    }
+
+    let mut out = BufWriter::new(File::create(file_path)?);
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "element vertex {}", vertices.items.len())?;
+    for axis in ["x", "y", "z"] {
+        writeln!(out, "property float {}", axis)?;
+    }
+    if colored {
+        for channel in ["red", "green", "blue"] {
+            writeln!(out, "property uchar {}", channel)?;
+        }
+    }
+    writeln!(out, "element face {}", faces.len())?;
+    writeln!(out, "property list uchar int vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    for (position, color) in vertices.items.iter() {
+        for value in position {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        if colored {
+            out.write_all(color)?;
+        }
+    }
+    for face in faces.iter() {
+        out.write_all(&[3])?;
+        for index in face {
+            out.write_all(&(*index as i32).to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+// hands out an index per distinct item, in first seen order
+struct Indexed<T> {
+    items: Vec<T>,
+    indices: HashMap<T, usize>,
+}
+
+impl<T> Default for Indexed<T> {
+    fn default() -> Self {
+        Indexed {
+            items: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Copy + Eq + std::hash::Hash> Indexed<T> {
+    fn index(&mut self, item: T) -> usize {
+        let items = &mut self.items;
+        *self.indices.entry(item).or_insert_with(|| {
+            items.push(item);
+            items.len() - 1
+        })
+    }
+}
+
+// copies of the triangles with `transform` applied, wound back the right way
+// if it mirrors them
+fn baked(mesh: &Mesh, transform: Option<&Matrix4<f32>>) -> Vec<Triangle> {
+    let transform = match transform {
+        Some(transform) => transform,
+        None => return mesh.triangles.clone(),
+    };
+    let mirrored = transform.fixed_view::<3, 3>(0, 0).determinant() < 0.;
+
+    mesh.triangles
+        .iter()
+        .map(|triangle| {
+            let mut baked = Triangle {
+                vertices: triangle
+                    .vertices
+                    .map(|v| multiply_matrix_vec(transform, &v)),
+                normals: triangle
+                    .normals
+                    .map(|normals| normals.map(|n| transform_normal(transform, &n))),
+                ..triangle.clone()
+            };
+            if mirrored {
+                baked.vertices.swap(1, 2);
+                if let Some(normals) = baked.normals.as_mut() {
+                    normals.swap(1, 2);
+                }
+                if let Some(colors) = baked.vertex_colors.as_mut() {
+                    colors.swap(1, 2);
+                }
+                if let Some(uvs) = baked.uvs.as_mut() {
+                    uvs.swap(1, 2);
+                }
+            }
+            baked
+        })
+        .collect()
+}
+
+fn corner_colors(triangle: &Triangle) -> [SolidSource; 3] {
+    match (triangle.vertex_colors, triangle.base_color) {
+        (Some(colors), _) => colors,
+        (None, Some(color)) => [color; 3],
+        // what every other face was written as, the default triangle color
+        (None, None) => [Triangle::default().color; 3],
+    }
+}
+
+fn material_name(color: &Option<[u8; 3]>) -> String {
+    match color {
+        Some([r, g, b]) => format!("color_{:02x}{:02x}{:02x}", r, g, b),
+        None => String::from("default"),
+    }
+}
+
+// rust prints the shortest form that parses back to the same f32
+fn float(bits: u32) -> f32 {
+    f32::from_bits(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meshes::cube::_get_cube_mesh;
+    use crate::meshes::initialize_mesh::parse_model;
+
+    // written with save_mesh and read back by the loader the extension picks
+    fn round_trip(mesh: &Mesh, extension: &str) -> Mesh {
+        let directory = std::env::temp_dir().join(format!("export-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(format!("cube.{}", extension));
+        let label = path.to_string_lossy().into_owned();
+
+        save_mesh(mesh, None, &label).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let loaded = parse_model(&bytes, &label, &mut |name| {
+            std::fs::read(directory.join(name)).ok()
+        });
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        loaded.unwrap()
+    }
+
+    #[test]
+    fn saved_meshes_load_back_the_same() {
+        let mut mesh = _get_cube_mesh();
+        for triangle in mesh.triangles.iter_mut() {
+            let normal = derive_normal(triangle);
+            triangle.normals = Some([normal; 3]);
+        }
+
+        for extension in ["obj", "stl", "ply"] {
+            let loaded = round_trip(&mesh, extension);
+            assert_eq!(
+                loaded.triangles.len(),
+                mesh.triangles.len(),
+                "{}",
+                extension
+            );
+            for (saved, loaded) in mesh.triangles.iter().zip(loaded.triangles.iter()) {
+                assert_eq!(saved.vertices, loaded.vertices, "{}", extension);
+            }
+        }
+
+        // obj is the only one of them with corner normals
+        let loaded = round_trip(&mesh, "obj");
+        for (saved, loaded) in mesh.triangles.iter().zip(loaded.triangles.iter()) {
+            let (saved, loaded) = (saved.normals.unwrap(), loaded.normals.unwrap());
+            for (a, b) in saved.iter().zip(loaded.iter()) {
+                assert!((a - b).abs().max() < 1e-6, "{:?} came back as {:?}", a, b);
+            }
+        }
+    }
+}
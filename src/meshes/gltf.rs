@@ -0,0 +1,488 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gltf::buffer;
+use gltf::image::{self, Format};
+use gltf::mesh::Mode;
+use gltf::texture::WrappingMode;
+use gltf::{Document, Gltf, Node, Primitive};
+use nalgebra::base::{Matrix4, Vector4};
+use raqote::SolidSource;
+
+use crate::camera::view::{Camera, MAX_PITCH};
+use crate::drawing::colors::average_color;
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::Triangle;
+use crate::linear_algebra::vectors::{dot_product, normalize_vec};
+use crate::meshes::load_error::LoadError;
+
+// everything the engine keeps from a gltf file, flattened into world space
+pub struct GltfScene {
+    // every primitive of every node in the scene, node transforms baked in
+    pub mesh: Mesh,
+    // one per camera node, in the file's space, before main's world matrix.
+    // the projection is the engine's own, only the placement carries over
+    pub cameras: Vec<Camera>,
+    // things in the file that were skipped, worth showing to whoever loaded it
+    pub warnings: Vec<String>,
+    // buffers and images it pointed to outside itself, empty for .glb and slices
+    pub files: Vec<PathBuf>,
+}
+
+// walks the node tree collecting triangles and cameras
+struct Importer<'a> {
+    buffers: &'a [buffer::Data],
+    images: &'a [image::Data],
+    triangles: Vec<Triangle>,
+    cameras: Vec<Camera>,
+    warnings: Vec<String>,
+}
+
+// .gltf with embedded or external buffers and images, or .glb
+pub fn load_gltf(file_path: &str) -> Result<GltfScene, LoadError> {
+    let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let (document, buffers, images) = import(&fs::read(file_path)?, file_path, Some(directory))?;
+
+    let buffer_uris = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            buffer::Source::Uri(uri) => Some(uri),
+            buffer::Source::Bin => None,
+        });
+    let image_uris = document.images().filter_map(|image| match image.source() {
+        image::Source::Uri { uri, .. } => Some(uri),
+        image::Source::View { .. } => None,
+    });
+
+    let mut scene = build_scene(&document, &buffers, &images);
+    scene.files = buffer_uris
+        .chain(image_uris)
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| directory.join(uri))
+        .collect();
+
+    Ok(scene)
+}
+
+// a .glb or .gltf already in memory, anything it points to outside itself can't be found
+pub fn import_gltf_slice(bytes: &[u8], label: &str) -> Result<GltfScene, LoadError> {
+    let (document, buffers, images) = import(bytes, label, None)?;
+
+    Ok(build_scene(&document, &buffers, &images))
+}
+
+// gltf::import without reading or parsing the file again. the crate refuses
+// required extensions it doesn't know with a terse validation error, so the
+// json is checked for them first and validated after. `base` is where files
+// the document names are looked for, None leaves only what's inside it
+fn import(
+    bytes: &[u8],
+    label: &str,
+    base: Option<&Path>,
+) -> Result<(Document, Vec<buffer::Data>, Vec<image::Data>), LoadError> {
+    let Gltf { document, blob } = Gltf::from_slice_without_validation(bytes)?;
+    let required: Vec<&str> = document.extensions_required().collect();
+    if !required.is_empty() {
+        return Err(LoadError::Parse(format!(
+            "{}: requires unsupported extensions {}",
+            label,
+            required.join(", ")
+        )));
+    }
+
+    let document = Document::from_json(document.into_json())?;
+    let buffers = gltf::import_buffers(&document, base, blob)?;
+    let images = gltf::import_images(&document, base, &buffers)?;
+
+    Ok((document, buffers, images))
+}
+
+fn build_scene(document: &Document, buffers: &[buffer::Data], images: &[image::Data]) -> GltfScene {
+    let mut importer = Importer {
+        buffers,
+        images,
+        triangles: Vec::new(),
+        cameras: Vec::new(),
+        warnings: document
+            .extensions_used()
+            .map(|extension| format!("ignoring unsupported extension {}", extension))
+            .collect(),
+    };
+
+    // files without a scene list are allowed, they just have nothing to show
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                importer.visit(&node, &Matrix4::identity());
+            }
+        }
+        None => importer.warnings.push(String::from("no scenes")),
+    }
+
+    GltfScene {
+        mesh: Mesh {
+            triangles: importer.triangles,
+        },
+        cameras: importer.cameras,
+        warnings: importer.warnings,
+        files: Vec::new(),
+    }
+}
+
+impl Importer<'_> {
+    // transforms are column vector, the way gltf and nalgebra both write them
+    fn visit(&mut self, node: &Node, parent: &Matrix4<f32>) {
+        let transform = parent * Matrix4::from(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, &transform);
+            }
+        }
+        if node.camera().is_some() {
+            self.cameras.push(camera_from_transform(&transform));
+        }
+
+        for child in node.children() {
+            self.visit(&child, &transform);
+        }
+    }
+
+    fn add_primitive(&mut self, primitive: &Primitive, transform: &Matrix4<f32>) {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+        let positions: Vec<Vector4<f32>> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|[x, y, z]| {
+                    let point = transform * Vector4::new(x, y, z, 1.);
+                    Vector4::new(point.x, point.y, point.z, 1.)
+                })
+                .collect(),
+            None => return,
+        };
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let faces: Vec<[usize; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|face| [face[0], face[1], face[2]])
+                .collect(),
+            // every other strip triangle is flipped to keep the winding
+            Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+                .map(|i| match i % 2 {
+                    0 => [indices[i], indices[i + 1], indices[i + 2]],
+                    _ => [indices[i], indices[i + 2], indices[i + 1]],
+                })
+                .collect(),
+            Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+                .map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+            mode => {
+                self.warnings.push(format!(
+                    "skipping a {:?} primitive, only faces are drawn",
+                    mode
+                ));
+                return;
+            }
+        };
+        if let Some(index) = faces.iter().flatten().find(|i| **i >= positions.len()) {
+            self.warnings.push(format!(
+                "skipping a primitive that uses missing vertex {}",
+                index
+            ));
+            return;
+        }
+
+        let colors = self.vertex_colors(primitive, &reader, positions.len());
+        let uvs: Option<Vec<[f32; 2]>> = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().collect())
+            .filter(|uvs: &Vec<[f32; 2]>| uvs.len() == positions.len());
+        // mirroring transforms turn the faces inside out
+        let mirrored = transform.determinant() < 0.;
+
+        for mut face in faces {
+            if mirrored {
+                face.swap(1, 2);
+            }
+            let mut triangle = Triangle {
+                vertices: face.map(|i| positions[i]),
+                uvs: uvs.as_ref().map(|uvs| face.map(|i| uvs[i])),
+                ..Default::default()
+            };
+            match &colors {
+                Colors::Flat(color) => triangle.base_color = Some(*color),
+                Colors::PerVertex(colors) => {
+                    let corners = face.map(|i| colors[i]);
+                    triangle.base_color = Some(average_color(&corners));
+                    triangle.vertex_colors = Some(corners);
+                }
+            }
+            self.triangles.push(triangle);
+        }
+    }
+
+    // base color factor, times the base color texture and COLOR_0 where there are any
+    fn vertex_colors<'s, F>(
+        &mut self,
+        primitive: &Primitive,
+        reader: &gltf::mesh::Reader<'s, 's, F>,
+        count: usize,
+    ) -> Colors
+    where
+        F: Clone + Fn(gltf::Buffer<'s>) -> Option<&'s [u8]>,
+    {
+        let pbr = primitive.material().pbr_metallic_roughness();
+        let factor = pbr.base_color_factor();
+
+        let texture = pbr.base_color_texture().and_then(|info| {
+            let texture = info.texture();
+            let image = &self.images[texture.source().index()];
+            let uvs = reader.read_tex_coords(info.tex_coord())?;
+            match channels(image.format) {
+                Some(_) => Some((image, texture.sampler(), uvs.into_f32())),
+                None => {
+                    self.warnings.push(format!(
+                        "ignoring a base color texture in {:?}, only 8 bit formats are read",
+                        image.format
+                    ));
+                    None
+                }
+            }
+        });
+        let vertex_colors = reader.read_colors(0).map(|colors| colors.into_rgba_f32());
+
+        if texture.is_none() && vertex_colors.is_none() {
+            return Colors::Flat(to_source(factor));
+        }
+
+        let mut colors: Vec<[f32; 4]> = vec![factor; count];
+        if let Some((image, sampler, uvs)) = texture {
+            for (color, [u, v]) in colors.iter_mut().zip(uvs) {
+                let x = wrap(u, sampler.wrap_s(), image.width);
+                let y = wrap(v, sampler.wrap_t(), image.height);
+                if let Some(sample) = texel(image, x, y) {
+                    multiply(color, sample);
+                }
+            }
+        }
+        if let Some(vertex_colors) = vertex_colors {
+            for (color, sample) in colors.iter_mut().zip(vertex_colors) {
+                multiply(color, sample);
+            }
+        }
+
+        Colors::PerVertex(colors.into_iter().map(to_source).collect())
+    }
+}
+
+enum Colors {
+    Flat(SolidSource),
+    PerVertex(Vec<SolidSource>),
+}
+
+// gltf cameras look down their local -z with +y up
+fn camera_from_transform(transform: &Matrix4<f32>) -> Camera {
+    let axis = |x: f32, y: f32, z: f32| {
+        let v = transform * Vector4::new(x, y, z, 0.);
+        normalize_vec(&Vector4::new(v.x, v.y, v.z, 1.))
+    };
+    let (forward, up) = (axis(0., 0., -1.), axis(0., 1., 0.));
+    let position = transform * Vector4::new(0., 0., 0., 1.);
+
+    // inverse of Camera::look_dir, then the roll that turns its level up onto ours
+    let mut camera = Camera {
+        position: Vector4::new(position.x, position.y, position.z, 1.),
+        yaw: (-forward.x).atan2(forward.z).to_degrees(),
+        pitch: forward
+            .y
+            .clamp(-1., 1.)
+            .asin()
+            .to_degrees()
+            .clamp(-MAX_PITCH, MAX_PITCH),
+        roll: 0.,
+    };
+    camera.roll = dot_product(&up, &camera.right())
+        .atan2(dot_product(&up, &camera.up()))
+        .to_degrees();
+
+    camera
+}
+
+// texture coordinate -> pixel, following the sampler's wrapping
+fn wrap(coordinate: f32, mode: WrappingMode, size: u32) -> u32 {
+    let t = match mode {
+        WrappingMode::ClampToEdge => coordinate.clamp(0., 1.),
+        WrappingMode::Repeat => coordinate.rem_euclid(1.),
+        WrappingMode::MirroredRepeat => {
+            let t = coordinate.rem_euclid(2.);
+            match t > 1. {
+                true => 2. - t,
+                false => t,
+            }
+        }
+    };
+
+    ((t * size as f32) as u32).min(size.saturating_sub(1))
+}
+
+// only 8 bits per channel formats are read
+fn channels(format: Format) -> Option<u32> {
+    match format {
+        Format::R8 => Some(1),
+        Format::R8G8 => Some(2),
+        Format::R8G8B8 => Some(3),
+        Format::R8G8B8A8 => Some(4),
+        _ => None,
+    }
+}
+
+// linear rgba of one pixel
+fn texel(image: &image::Data, x: u32, y: u32) -> Option<[f32; 4]> {
+    let channels = channels(image.format)?;
+    let start = ((y * image.width + x) * channels) as usize;
+    let pixel = image.pixels.get(start..start + channels as usize)?;
+
+    let channel = |i: usize| to_linear(pixel[i]);
+    Some(match channels {
+        1 => [channel(0), channel(0), channel(0), 1.],
+        2 => [channel(0), channel(0), channel(0), pixel[1] as f32 / 255.],
+        3 => [channel(0), channel(1), channel(2), 1.],
+        _ => [channel(0), channel(1), channel(2), pixel[3] as f32 / 255.],
+    })
+}
+
+fn multiply(color: &mut [f32; 4], by: [f32; 4]) {
+    for (channel, by) in color.iter_mut().zip(by) {
+        *channel *= by;
+    }
+}
+
+// gltf colors are linear and the engine's are srgb
+fn to_linear(value: u8) -> f32 {
+    (value as f32 / 255.).powf(2.2)
+}
+
+// the renderer has no transparency, so alpha is dropped
+fn to_source(color: [f32; 4]) -> SolidSource {
+    let srgb = |value: f32| (value.clamp(0., 1.).powf(1. / 2.2) * 255.).round() as u8;
+
+    SolidSource::from_unpremultiplied_argb(0xff, srgb(color[0]), srgb(color[1]), srgb(color[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a strip and a fan covering the same unit square, both wound to face +z
+    const STRIP: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]];
+    const FAN: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+
+    // a .glb holding `json` and `bin`, each chunk padded to 4 bytes
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut bytes = b"glTF".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        for (chunk, kind) in [(json, b"JSON"), (bin, b"BIN\0")] {
+            bytes.extend((chunk.len() as u32).to_le_bytes());
+            bytes.extend(kind);
+            bytes.extend(chunk);
+        }
+        bytes
+    }
+
+    fn square(extensions_required: &str) -> Vec<u8> {
+        let bin: Vec<u8> = STRIP
+            .iter()
+            .chain(FAN.iter())
+            .flatten()
+            .flat_map(|coordinate| coordinate.to_le_bytes())
+            .collect();
+        let accessor = |offset: usize| {
+            format!(
+                r#"{{"bufferView": 0, "byteOffset": {}, "componentType": 5126, "count": 4,
+                    "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]}}"#,
+                offset
+            )
+        };
+        let json = format!(
+            r#"{{"asset": {{"version": "2.0"}}, {}
+                "scene": 0, "scenes": [{{"nodes": [0]}}], "nodes": [{{"mesh": 0}}],
+                "meshes": [{{"primitives": [
+                    {{"attributes": {{"POSITION": 0}}, "mode": 5}},
+                    {{"attributes": {{"POSITION": 1}}, "mode": 6}}]}}],
+                "accessors": [{}, {}],
+                "bufferViews": [{{"buffer": 0, "byteLength": {}}}],
+                "buffers": [{{"byteLength": {}}}]}}"#,
+            extensions_required,
+            accessor(0),
+            accessor(48),
+            bin.len(),
+            bin.len()
+        );
+        glb(&json, &bin)
+    }
+
+    #[test]
+    fn strips_and_fans_keep_their_winding() {
+        let scene = import_gltf_slice(&square(""), "square.glb").unwrap();
+        assert!(scene.warnings.is_empty(), "{:?}", scene.warnings);
+
+        let corners: Vec<[[f32; 3]; 3]> = scene
+            .mesh
+            .triangles
+            .iter()
+            .map(|triangle| {
+                triangle
+                    .vertices
+                    .map(|vertex| [vertex.x, vertex.y, vertex.z])
+            })
+            .collect();
+        assert_eq!(
+            corners,
+            [
+                [STRIP[0], STRIP[1], STRIP[2]],
+                [STRIP[1], STRIP[3], STRIP[2]],
+                [FAN[0], FAN[1], FAN[2]],
+                [FAN[0], FAN[2], FAN[3]],
+            ]
+        );
+
+        for [a, b, c] in corners {
+            let ab = [b[0] - a[0], b[1] - a[1]];
+            let ac = [c[0] - a[0], c[1] - a[1]];
+            assert!(
+                ab[0] * ac[1] - ab[1] * ac[0] > 0.,
+                "{:?} faces away",
+                [a, b, c]
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_required_extensions_are_named() {
+        let bytes = square(r#""extensionsRequired": ["KHR_draco_mesh_compression"],"#);
+        match import_gltf_slice(&bytes, "draco.glb") {
+            Err(LoadError::Parse(message)) => {
+                assert!(
+                    message.contains("KHR_draco_mesh_compression"),
+                    "{}",
+                    message
+                )
+            }
+            Err(e) => panic!("wrong error {}", e),
+            Ok(_) => panic!("draco.glb was accepted"),
+        }
+    }
+}
@@ -1,67 +1,338 @@
+use image::RgbaImage;
 use nalgebra::Vector4;
+use raqote::SolidSource;
 
+use crate::camera::view::Camera;
+use crate::drawing::colors::average_color;
+use crate::linear_algebra::vectors::normalize_vec;
+use crate::meshes::archive::{load_from_archive, split_archive_path};
+use crate::meshes::cache::load_cached;
+use crate::meshes::gltf::{load_gltf, GltfScene};
+use crate::meshes::load_error::LoadError;
+use crate::meshes::ply::parse_ply;
+use crate::meshes::stl::parse_stl;
 use crate::{Mesh, Triangle};
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-pub fn get_mesh(file_path: &str) -> Mesh {
+// a mesh and the cameras its file placed, only gltf has any. they're in the
+// file's space, the same as the mesh before it's prepared
+pub struct Model {
+    pub mesh: Mesh,
+    pub cameras: Vec<Camera>,
+}
+
+impl From<Mesh> for Model {
+    fn from(mesh: Mesh) -> Self {
+        Model {
+            mesh,
+            cameras: Vec::new(),
+        }
+    }
+}
+
+// picks a loader by file extension, anything else is read as obj.
+// archive.zip, or archive.zip#inner/path.obj to pick one model, loads from inside a zip.
+// obj, stl and ply files go through a binary cache written next to them.
+// also adds every file on disk the mesh is read from to `sources`,
+// the model or archive first, then the mtl libraries, textures and buffers it
+// names. they're added even when loading fails, so a fix can be noticed
+pub fn load_mesh_tracked(file_path: &str, sources: &mut Vec<PathBuf>) -> Result<Model, LoadError> {
+    if let Some((archive, inner)) = split_archive_path(file_path) {
+        sources.push(PathBuf::from(archive));
+        return load_from_archive(archive, inner);
+    }
+    sources.push(PathBuf::from(file_path));
+
+    match extension(file_path).as_deref() {
+        Some("gltf") | Some("glb") => {
+            let mut scene = load_gltf(file_path)?;
+            sources.append(&mut scene.files);
+            Ok(report_warnings(scene, file_path))
+        }
+        _ => Ok(load_cached(file_path, sources)?.into()),
+    }
+}
+
+pub fn extension(file_path: &str) -> Option<String> {
+    Path::new(file_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+// the engine keeps the mesh and cameras, whatever was skipped is worth a line on stderr
+pub fn report_warnings(scene: GltfScene, label: &str) -> Model {
+    for warning in scene.warnings.iter() {
+        eprintln!("{}: {}", label, warning);
+    }
+    Model {
+        mesh: scene.mesh,
+        cameras: scene.cameras,
+    }
+}
+
+// stl, ply or obj bytes by the extension in `label`, anything else is read as obj.
+// `read_file` is only asked for an obj's side files, see parse_obj
+pub fn parse_model(
+    bytes: &[u8],
+    label: &str,
+    read_file: &mut dyn FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<Mesh, LoadError> {
+    let parse_error = |msg: String| LoadError::Parse(format!("{}: {}", label, msg));
+
+    match extension(label).as_deref() {
+        Some("stl") => parse_stl(bytes).map_err(parse_error),
+        Some("ply") => parse_ply(bytes).map_err(parse_error),
+        _ => parse_obj(&String::from_utf8_lossy(bytes), label, read_file),
+    }
+}
+
+#[derive(Default)]
+struct Material {
+    // Kd
+    color: Option<SolidSource>,
+    // map_Kd, tinted by Kd and sampled at each corner's uv
+    texture: Option<RgbaImage>,
+}
+
+// v, vt, vn and f lines, polygons are split into fans. corners given a vn keep it
+// as their normal when the whole face has them. `read_file` fetches the mtl libraries and textures the
+// obj names, relative to it, None if they aren't there
+pub fn parse_obj(
+    text: &str,
+    label: &str,
+    read_file: &mut dyn FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<Mesh, LoadError> {
     let mut mesh: Mesh = Mesh {
         triangles: Vec::new(),
     };
 
     let mut vertices_list: Vec<Vector4<f32>> = Vec::new();
+    let mut uvs_list: Vec<[f32; 2]> = Vec::new();
+    let mut normals_list: Vec<Vector4<f32>> = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut material: Option<String> = None;
 
-    if let Ok(lines) = read_lines(file_path) {
-        // Consumes the iterator, returns an (Optional) String
-        for line in lines {
-            if let Ok(ip) = line {
-                if &ip[..1] == "v" {
-                    let full_line: String = String::from(&ip[2..]);
-                    let split_str: Vec<&str> = full_line.split(' ').collect();
-                    let mut split_float: Vec<f32> = Vec::new();
-
-                    for i in 0..3 {
-                        split_float.push(split_str[i].parse::<f32>().unwrap());
-                    }
+    let error = |number: usize, msg: &str| {
+        LoadError::Parse(format!("{}: line {}: {}", label, number + 1, msg))
+    };
 
-                    vertices_list.push(Vector4::new(
-                        split_float[0],
-                        split_float[1],
-                        split_float[2],
-                        1.,
-                    ));
-                } else if &ip[..1] == "f" {
-                    let full_line: String = String::from(&ip[2..]);
-                    let split_str: Vec<&str> = full_line.split(' ').collect();
-                    let mut split_int: Vec<usize> = Vec::new();
-                    for i in 0..3 {
-                        split_int.push(split_str[i].parse::<usize>().unwrap());
-                    }
-                    let first = split_int[0] - 1;
-                    let second = split_int[1] - 1;
-                    let third = split_int[2] - 1;
-
-                    let tri: Triangle = Triangle {
-                        vertices: [
-                            vertices_list[first],
-                            vertices_list[second],
-                            vertices_list[third],
-                        ],
+    for (number, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let point =
+                    read_floats(&mut tokens, 3).ok_or_else(|| error(number, "bad vertex"))?;
+                vertices_list.push(Vector4::new(point[0], point[1], point[2], 1.));
+            }
+            Some("vt") => {
+                // v is optional and defaults to 0
+                let u = read_floats(&mut tokens, 1).ok_or_else(|| error(number, "bad uv"))?;
+                let v = read_floats(&mut tokens, 1).map_or(0., |v| v[0]);
+                uvs_list.push([u[0], v]);
+            }
+            Some("vn") => {
+                let normal =
+                    read_floats(&mut tokens, 3).ok_or_else(|| error(number, "bad normal"))?;
+                normals_list.push(normalize_vec(&Vector4::new(
+                    normal[0], normal[1], normal[2], 1.,
+                )));
+            }
+            Some("f") => {
+                // each corner is v, v/vt, v//vn or v/vt/vn, negative counts back from the end
+                let mut corners: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let vertex = parts
+                        .next()
+                        .and_then(|part| resolve(part, vertices_list.len()))
+                        .ok_or_else(|| error(number, "bad face vertex"))?;
+                    let uv = match parts.next() {
+                        Some(part) if !part.is_empty() => Some(
+                            resolve(part, uvs_list.len())
+                                .ok_or_else(|| error(number, "bad face uv"))?,
+                        ),
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(part) if !part.is_empty() => Some(
+                            resolve(part, normals_list.len())
+                                .ok_or_else(|| error(number, "bad face normal"))?,
+                        ),
+                        _ => None,
+                    };
+                    corners.push((vertex, uv, normal));
+                }
+
+                let material = material.as_ref().and_then(|name| materials.get(name));
+                for i in 1..corners.len().saturating_sub(1) {
+                    let face = [corners[0], corners[i], corners[i + 1]];
+                    let uvs = match face.map(|(_, uv, _)| uv.map(|uv| uvs_list[uv])) {
+                        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                        _ => None,
+                    };
+                    let normals = match face.map(|(_, _, n)| n.map(|n| normals_list[n])) {
+                        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                        _ => None,
+                    };
+                    let mut tri: Triangle = Triangle {
+                        vertices: face.map(|(vertex, _, _)| vertices_list[vertex]),
+                        base_color: material.and_then(|material| material.color),
+                        uvs,
+                        normals,
+                        fan: i > 1,
                         ..Default::default()
                     };
+                    if let (Some(material), Some(uvs)) = (material, uvs) {
+                        if let Some(colors) = textured(material, &uvs) {
+                            tri.base_color = Some(average_color(&colors));
+                            tri.vertex_colors = Some(colors);
+                        }
+                    }
                     mesh.triangles.push(tri);
                 }
             }
+            Some("mtllib") => {
+                // a missing library just leaves faces uncolored
+                for name in tokens {
+                    let name = side_file(name);
+                    if let Some(bytes) = read_file(&name) {
+                        let text = String::from_utf8_lossy(&bytes).into_owned();
+                        // textures are relative to the library, not the obj
+                        let directory = match name.rsplit_once('/') {
+                            Some((directory, _)) => format!("{}/", directory),
+                            None => String::new(),
+                        };
+                        materials.extend(read_materials(&text, &mut |texture| {
+                            read_file(&format!("{}{}", directory, texture))
+                        }));
+                    }
+                }
+            }
+            Some("usemtl") => {
+                material = tokens.next().map(String::from);
+            }
+            _ => {}
         }
     }
-    mesh
+
+    Ok(mesh)
 }
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+// newmtl name -> its Kd and map_Kd
+fn read_materials(
+    text: &str,
+    read_file: &mut dyn FnMut(&str) -> Option<Vec<u8>>,
+) -> HashMap<String, Material> {
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut name: Option<String> = None;
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        let current = name
+            .as_ref()
+            .map(|name| materials.entry(name.clone()).or_default());
+
+        match (tokens.next(), current) {
+            (Some("newmtl"), _) => name = tokens.next().map(String::from),
+            (Some("Kd"), Some(material)) => {
+                if let Some(kd) = read_floats(&mut tokens, 3) {
+                    let channel = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+                    material.color = Some(SolidSource::from_unpremultiplied_argb(
+                        0xff,
+                        channel(kd[0]),
+                        channel(kd[1]),
+                        channel(kd[2]),
+                    ));
+                }
+            }
+            (Some("map_Kd"), Some(material)) => {
+                let file = texture_name(tokens);
+                material.texture = read_file(&side_file(&file))
+                    .and_then(|bytes| image::load_from_memory(&bytes).ok())
+                    .map(|image| image.to_rgba8());
+            }
+            _ => {}
+        }
+    }
+
+    materials
+}
+
+// skips options like -s 1 1 1 or -clamp on that come before the name,
+// which can have spaces in it
+fn texture_name<'a>(tokens: impl Iterator<Item = &'a str>) -> String {
+    let mut name: Vec<&str> = Vec::new();
+    let mut in_option = false;
+    for token in tokens {
+        let argument = token.parse::<f32>().is_ok() || token == "on" || token == "off";
+        match (name.is_empty(), token.starts_with('-') && !argument) {
+            (true, true) => in_option = true,
+            (true, false) if in_option && argument => {}
+            _ => name.push(token),
+        }
+    }
+
+    name.join(" ")
+}
+
+// corner colors from the material's texture, None without one
+fn textured(material: &Material, uvs: &[[f32; 2]; 3]) -> Option<[SolidSource; 3]> {
+    let texture = material.texture.as_ref()?;
+    if texture.width() == 0 || texture.height() == 0 {
+        return None;
+    }
+    let tint = material
+        .color
+        .unwrap_or(SolidSource::from_unpremultiplied_argb(
+            0xff, 0xff, 0xff, 0xff,
+        ));
+
+    Some(uvs.map(|[u, v]| {
+        // textures repeat, and v runs up from the bottom of the image
+        let x = ((u.rem_euclid(1.) * texture.width() as f32) as u32).min(texture.width() - 1);
+        let y =
+            (((1. - v.rem_euclid(1.)) * texture.height() as f32) as u32).min(texture.height() - 1);
+        let texel = texture.get_pixel(x, y);
+        let tinted = |channel: u8, by: u8| (channel as u32 * by as u32 / 255) as u8;
+
+        SolidSource::from_unpremultiplied_argb(
+            0xff,
+            tinted(texel[0], tint.r),
+            tinted(texel[1], tint.g),
+            tinted(texel[2], tint.b),
+        )
+    }))
+}
+
+// exporters on windows write backslashes
+fn side_file(name: &str) -> String {
+    name.replace('\\', "/")
+}
+
+fn read_floats<'a>(tokens: &mut impl Iterator<Item = &'a str>, count: usize) -> Option<Vec<f32>> {
+    let floats: Vec<f32> = tokens
+        .take(count)
+        .map(|token| token.parse::<f32>().ok())
+        .collect::<Option<Vec<f32>>>()?;
+
+    match floats.len() == count {
+        true => Some(floats),
+        false => None,
+    }
+}
+
+// 1 based obj index -> list index
+fn resolve(index: &str, len: usize) -> Option<usize> {
+    let index = index.parse::<i64>().ok()?;
+    let resolved = match index < 0 {
+        true => len as i64 + index,
+        false => index - 1,
+    };
+
+    match resolved >= 0 && (resolved as usize) < len {
+        true => Some(resolved as usize),
+        false => None,
+    }
 }
@@ -0,0 +1,49 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Image(image::ImageError),
+    Gltf(gltf::Error),
+    Zip(zip::result::ZipError),
+    Parse(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "couldn't read file: {}", e),
+            LoadError::Image(e) => write!(f, "couldn't decode image: {}", e),
+            LoadError::Gltf(e) => write!(f, "couldn't import gltf: {}", e),
+            LoadError::Zip(e) => write!(f, "couldn't read archive: {}", e),
+            LoadError::Parse(msg) => write!(f, "couldn't parse file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for LoadError {
+    fn from(e: image::ImageError) -> Self {
+        LoadError::Image(e)
+    }
+}
+
+impl From<gltf::Error> for LoadError {
+    fn from(e: gltf::Error) -> Self {
+        LoadError::Gltf(e)
+    }
+}
+
+impl From<zip::result::ZipError> for LoadError {
+    fn from(e: zip::result::ZipError) -> Self {
+        LoadError::Zip(e)
+    }
+}
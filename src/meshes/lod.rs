@@ -0,0 +1,187 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use nalgebra::base::{Matrix4, Vector4};
+
+use crate::camera::view::Camera;
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::matrices::multiply_matrix_vec;
+use crate::linear_algebra::vectors::{add_vec, mult_vec, sub_vec, vec_magnitude};
+use crate::meshes::simplify::Decimator;
+use crate::HEIGHT;
+
+// each level has half the triangles of the one before, down to about this many
+const MIN_TRIANGLES: usize = 256;
+const MAX_LEVELS: usize = 6;
+// how far a level may stray from the full mesh on screen, in pixels
+const PIXEL_ERROR: f32 = 0.5;
+
+pub struct Lod {
+    pub mesh: Mesh,
+    // Decimator::error, in model units
+    pub error: f32,
+}
+
+// coarser versions of a mesh, with the one to draw picked by how big the mesh
+// looks on screen. level 0 is the mesh itself, which the chain doesn't keep a copy of
+pub struct LodChain {
+    pub levels: Vec<Lod>,
+    // level to draw whatever the size, for comparing them
+    pub fixed: Option<usize>,
+    // bounding sphere of the full mesh
+    center: Vector4<f32>,
+    radius: f32,
+    // levels being built on another thread, see rebuild
+    pending: Option<Receiver<LodChain>>,
+    // the mesh was edited while they were, so they'll come back out of date
+    stale: bool,
+}
+
+impl Default for LodChain {
+    fn default() -> Self {
+        LodChain {
+            levels: Vec::new(),
+            fixed: None,
+            center: Vector4::new(0., 0., 0., 1.),
+            radius: 0.,
+            pending: None,
+            stale: false,
+        }
+    }
+}
+
+impl LodChain {
+    // one decimation run, snapshotted at every halving. a mesh already small
+    // gets no levels. this is slow on big meshes, the viewer uses rebuild
+    pub fn build(mesh: &Mesh) -> LodChain {
+        let mut levels: Vec<Lod> = Vec::new();
+        let mut decimator = Decimator::new(mesh);
+        let mut target = mesh.triangles.len() / 2;
+
+        while levels.len() < MAX_LEVELS && target >= MIN_TRIANGLES {
+            let before = decimator.faces_left();
+            let error = decimator.collapse_to(target, f32::INFINITY);
+            // stuck on collapses that would fold or tear it, nothing coarser is coming
+            if decimator.faces_left() * 10 > before * 9 {
+                break;
+            }
+            levels.push(Lod {
+                mesh: decimator.mesh(),
+                error,
+            });
+            target = decimator.faces_left() / 2;
+        }
+
+        let (center, radius) = match mesh.bounds(&Matrix4::identity()) {
+            Some((min, max)) => (
+                mult_vec(&add_vec(&min, &max), 0.5),
+                vec_magnitude(&sub_vec(&max, &min)) * 0.5,
+            ),
+            None => (Vector4::new(0., 0., 0., 1.), 0.),
+        };
+
+        LodChain {
+            levels,
+            center,
+            radius,
+            ..Default::default()
+        }
+    }
+
+    // for a mesh that changed. the levels are built on another thread, until poll
+    // swaps them in only the full mesh is drawn. a fixed level is kept if there
+    // still is one
+    pub fn rebuild(&mut self, mesh: &Mesh) {
+        self.levels.clear();
+        self.stale = false;
+
+        let (sender, pending) = mpsc::channel();
+        let mesh = mesh.clone();
+        // a build that's been replaced finds nobody listening and is thrown away
+        thread::spawn(move || sender.send(LodChain::build(&mesh)));
+        self.pending = Some(pending);
+    }
+
+    // call once a frame with the mesh as it is now, picks up finished levels
+    pub fn poll(&mut self, mesh: &Mesh) {
+        let built = match self.pending.as_ref().map(Receiver::try_recv) {
+            Some(Ok(built)) => built,
+            Some(Err(TryRecvError::Empty)) | None => return,
+            // the build panicked, carry on with just the full mesh
+            Some(Err(TryRecvError::Disconnected)) => {
+                self.pending = None;
+                return;
+            }
+        };
+        if self.stale {
+            self.rebuild(mesh);
+            return;
+        }
+
+        self.levels = built.levels;
+        self.center = built.center;
+        self.radius = built.radius;
+        self.fixed = self.fixed.filter(|&level| level < self.level_count());
+        self.pending = None;
+    }
+
+    // for edits that come out the same whatever the detail, like normals or
+    // winding, cheaper than decimating again. levels still being built missed
+    // it, so they're built again once they arrive
+    pub fn apply(&mut self, mut change: impl FnMut(&mut Mesh)) {
+        for lod in self.levels.iter_mut() {
+            change(&mut lod.mesh);
+        }
+        if self.pending.is_some() {
+            self.stale = true;
+        }
+    }
+
+    // levels including the full mesh
+    pub fn level_count(&self) -> usize {
+        self.levels.len() + 1
+    }
+
+    // the coarsest level whose error stays under PIXEL_ERROR at the mesh's size on
+    // screen, measured at the nearest point of its bounding sphere
+    pub fn select(
+        &self,
+        world_matrix: &Matrix4<f32>,
+        camera: &Camera,
+        projection_matrix: &Matrix4<f32>,
+    ) -> usize {
+        // a fixed level that's still being built draws as the full mesh
+        if let Some(level) = self.fixed {
+            return level.min(self.levels.len());
+        }
+
+        let world = multiply_matrix_vec(world_matrix, &self.center);
+        let depth = multiply_matrix_vec(&camera.view_matrix(), &world).z - self.radius;
+        // the camera is inside it or right up against it
+        if depth <= 0.1 {
+            return 0;
+        }
+        let pixels_per_unit = projection_matrix[(1, 1)] * 0.5 * HEIGHT as f32 / depth;
+
+        self.levels
+            .iter()
+            .rposition(|lod| lod.error * pixels_per_unit <= PIXEL_ERROR)
+            .map_or(0, |level| level + 1)
+    }
+
+    // picking by size, then every level from the full mesh down
+    pub fn cycle(&mut self) {
+        self.fixed = match self.fixed {
+            None => Some(0),
+            Some(level) if level + 1 < self.level_count() => Some(level + 1),
+            Some(_) => None,
+        };
+    }
+
+    pub fn mesh<'a>(&'a self, level: usize, full: &'a Mesh) -> &'a Mesh {
+        match level {
+            0 => full,
+            level => &self.levels[level - 1].mesh,
+        }
+    }
+}
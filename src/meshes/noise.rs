@@ -0,0 +1,138 @@
+// classic 2D perlin noise, layered into fractal noise for terrain heights
+
+pub struct NoiseSettings {
+    pub seed: u32,
+    pub octaves: u32,
+    // features per unit of terrain at the first octave
+    pub frequency: f32,
+    // amplitude multiplier between octaves
+    pub persistence: f32,
+    // frequency multiplier between octaves
+    pub lacunarity: f32,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        NoiseSettings {
+            seed: 1,
+            octaves: 5,
+            frequency: 0.15,
+            persistence: 0.5,
+            lacunarity: 2.,
+        }
+    }
+}
+
+pub struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u32) -> Self {
+        let mut table: [u8; 256] = [0; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+
+        // fisher-yates shuffle driven by a xorshift generator, so the same
+        // seed always gives the same terrain
+        let mut state: u32 = seed.wrapping_mul(0x9e37_79b9) | 1;
+        for i in (1..256).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let j = (state % (i as u32 + 1)) as usize;
+            table.swap(i, j);
+        }
+
+        let mut permutation: [u8; 512] = [0; 512];
+        for (i, entry) in permutation.iter_mut().enumerate() {
+            *entry = table[i % 256];
+        }
+
+        Perlin { permutation }
+    }
+
+    // roughly in -1..1
+    pub fn get(&self, x: f32, y: f32) -> f32 {
+        let xi = x.floor() as i32 & 255;
+        let yi = y.floor() as i32 & 255;
+        let xf = x - x.floor();
+        let yf = y - y.floor();
+
+        let u = fade(xf);
+        let v = fade(yf);
+
+        let p = &self.permutation;
+        let aa = p[p[xi as usize] as usize + yi as usize];
+        let ab = p[p[xi as usize] as usize + yi as usize + 1];
+        let ba = p[p[xi as usize + 1] as usize + yi as usize];
+        let bb = p[p[xi as usize + 1] as usize + yi as usize + 1];
+
+        let x1 = lerp(grad(aa, xf, yf), grad(ba, xf - 1., yf), u);
+        let x2 = lerp(grad(ab, xf, yf - 1.), grad(bb, xf - 1., yf - 1.), u);
+
+        lerp(x1, x2, v)
+    }
+
+    // sums octaves of noise and normalizes back into -1..1
+    pub fn fractal(&self, x: f32, y: f32, settings: &NoiseSettings) -> f32 {
+        let mut total = 0.;
+        let mut max_amplitude = 0.;
+        let mut amplitude = 1.;
+        let mut frequency = settings.frequency;
+
+        for _ in 0..settings.octaves.max(1) {
+            total += self.get(x * frequency, y * frequency) * amplitude;
+            max_amplitude += amplitude;
+            amplitude *= settings.persistence;
+            frequency *= settings.lacunarity;
+        }
+
+        total / max_amplitude
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn grad(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(perlin: &Perlin) -> Vec<f32> {
+        (0..64)
+            .map(|i| perlin.fractal(i as f32 * 1.37, i as f32 * 0.61, &NoiseSettings::default()))
+            .collect()
+    }
+
+    #[test]
+    fn a_seed_always_gives_the_same_noise() {
+        assert_eq!(samples(&Perlin::new(7)), samples(&Perlin::new(7)));
+        assert_ne!(samples(&Perlin::new(7)), samples(&Perlin::new(8)));
+
+        for value in samples(&Perlin::new(7)) {
+            assert!((-1. ..=1.).contains(&value), "{} is out of range", value);
+        }
+        // the lattice itself is always flat
+        assert_eq!(Perlin::new(7).get(3., 5.), 0.);
+    }
+}
@@ -0,0 +1,387 @@
+use std::str::SplitWhitespace;
+
+use nalgebra::Vector4;
+use raqote::SolidSource;
+
+use crate::drawing::colors::average_color;
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::Triangle;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    // both the original names and the sized ones newer exporters write
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::Char),
+            "uchar" | "uint8" => Some(Scalar::UChar),
+            "short" | "int16" => Some(Scalar::Short),
+            "ushort" | "uint16" => Some(Scalar::UShort),
+            "int" | "int32" => Some(Scalar::Int),
+            "uint" | "uint32" => Some(Scalar::UInt),
+            "float" | "float32" => Some(Scalar::Float),
+            "double" | "float64" => Some(Scalar::Double),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::Char | Scalar::UChar => 1,
+            Scalar::Short | Scalar::UShort => 2,
+            Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+            Scalar::Double => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    // count type, then item type
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// the data after end_header, read one value at a time in header order
+enum Body<'a> {
+    Ascii(SplitWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        offset: usize,
+        big_endian: bool,
+    },
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Body::Ascii(tokens) => tokens
+                .next()
+                .and_then(|token| token.parse::<f64>().ok())
+                .ok_or(String::from("ran out of numbers")),
+            Body::Binary {
+                bytes,
+                offset,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                let mut raw: [u8; 8] = [0; 8];
+                let slice = bytes
+                    .get(*offset..*offset + size)
+                    .ok_or(String::from("file ends early"))?;
+                raw[..size].copy_from_slice(slice);
+                if *big_endian {
+                    raw[..size].reverse();
+                }
+                *offset += size;
+
+                let value = match scalar {
+                    Scalar::Char => raw[0] as i8 as f64,
+                    Scalar::UChar => raw[0] as f64,
+                    Scalar::Short => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    Scalar::UShort => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    Scalar::Int => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::UInt => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::Float => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::Double => f64::from_le_bytes(raw),
+                };
+                Ok(value)
+            }
+        }
+    }
+}
+
+// ascii and binary of either endianness. vertex red, green and blue (and alpha)
+// become Triangle::vertex_colors, polygons are split into fans
+pub fn parse_ply(bytes: &[u8]) -> Result<Mesh, String> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or(String::from("no end_header"))?;
+    // the body starts on the line after end_header
+    let body_start = bytes[end..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map(|newline| end + newline + 1)
+        .unwrap_or(bytes.len());
+
+    let (format, elements) = parse_header(&String::from_utf8_lossy(&bytes[..end]))?;
+    let text;
+    let mut body = match format {
+        Format::Ascii => {
+            text = String::from_utf8_lossy(&bytes[body_start..]);
+            Body::Ascii(text.split_whitespace())
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Body::Binary {
+            bytes: &bytes[body_start..],
+            offset: 0,
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut positions: Vec<Vector4<f32>> = Vec::new();
+    let mut colors: Vec<SolidSource> = Vec::new();
+    let mut faces: Vec<Vec<usize>> = Vec::new();
+
+    for element in elements.iter() {
+        for _ in 0..element.count {
+            // scalar values by name, the first list for faces
+            let mut values: Vec<(&str, f64, Scalar)> = Vec::new();
+            let mut list: Vec<usize> = Vec::new();
+
+            for property in element.properties.iter() {
+                match property {
+                    Property::Scalar(name, scalar) => {
+                        values.push((name, body.read(*scalar)?, *scalar));
+                    }
+                    Property::List(name, count, item) => {
+                        let count = body.read(*count)? as usize;
+                        let keep =
+                            list.is_empty() && (name == "vertex_indices" || name == "vertex_index");
+                        for _ in 0..count {
+                            let index = body.read(*item)?;
+                            if keep {
+                                list.push(index as usize);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let get = |wanted: &str| values.iter().find(|(name, _, _)| *name == wanted);
+            match element.name.as_str() {
+                "vertex" => {
+                    let coordinate = |axis: &str| {
+                        get(axis)
+                            .map(|(_, value, _)| *value as f32)
+                            .ok_or(format!("vertex has no {}", axis))
+                    };
+                    positions.push(Vector4::new(
+                        coordinate("x")?,
+                        coordinate("y")?,
+                        coordinate("z")?,
+                        1.,
+                    ));
+
+                    // integer channels are 0..255, float ones 0..1
+                    let channel = |name: &str| {
+                        get(name).map(|(_, value, scalar)| match scalar {
+                            Scalar::Float | Scalar::Double => (value * 255.).clamp(0., 255.) as u8,
+                            _ => value.clamp(0., 255.) as u8,
+                        })
+                    };
+                    if let (Some(r), Some(g), Some(b)) =
+                        (channel("red"), channel("green"), channel("blue"))
+                    {
+                        let a = channel("alpha").unwrap_or(0xff);
+                        colors.push(SolidSource::from_unpremultiplied_argb(a, r, g, b));
+                    }
+                }
+                "face" => faces.push(list),
+                _ => {}
+            }
+        }
+    }
+
+    // colors are all or nothing, a partly colored mesh is treated as uncolored
+    let colored = !colors.is_empty() && colors.len() == positions.len();
+    let mut triangles: Vec<Triangle> = Vec::with_capacity(faces.len());
+
+    for face in faces.iter() {
+        if let Some(index) = face.iter().find(|index| **index >= positions.len()) {
+            return Err(format!(
+                "face uses vertex {}, there are only {}",
+                index,
+                positions.len()
+            ));
+        }
+
+        for i in 1..face.len().saturating_sub(1) {
+            let corners = [face[0], face[i], face[i + 1]];
+            let mut triangle = Triangle {
+                vertices: corners.map(|corner| positions[corner]),
+                fan: i > 1,
+                ..Default::default()
+            };
+            if colored {
+                let corner_colors = corners.map(|corner| colors[corner]);
+                triangle.base_color = Some(average_color(&corner_colors));
+                triangle.vertex_colors = Some(corner_colors);
+            }
+            triangles.push(triangle);
+        }
+    }
+
+    Ok(Mesh { triangles })
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), String> {
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(String::from("missing the ply magic number"));
+    }
+
+    let mut format: Option<Format> = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for (number, line) in lines.enumerate() {
+        // the magic number was line 1
+        let number = number + 2;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.as_slice() {
+            ["format", name, ..] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("line {}: unknown format {}", number, name)),
+                });
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| format!("line {}: bad element count", number))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", "list", count, item, name] => {
+                let property = Property::List(
+                    name.to_string(),
+                    scalar(count, number)?,
+                    scalar(item, number)?,
+                );
+                push_property(&mut elements, property, number)?;
+            }
+            ["property", kind, name] => {
+                let property = Property::Scalar(name.to_string(), scalar(kind, number)?);
+                push_property(&mut elements, property, number)?;
+            }
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or(String::from("no format line"))?;
+
+    Ok((format, elements))
+}
+
+fn scalar(name: &str, number: usize) -> Result<Scalar, String> {
+    Scalar::parse(name).ok_or(format!("line {}: unknown type {}", number, name))
+}
+
+fn push_property(
+    elements: &mut [Element],
+    property: Property,
+    number: usize,
+) -> Result<(), String> {
+    elements
+        .last_mut()
+        .map(|element| element.properties.push(property))
+        .ok_or(format!("line {}: property before any element", number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORNERS: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+    const RED: [u8; 3] = [255, 0, 0];
+
+    fn header(format: &str) -> String {
+        format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n",
+            format
+        )
+    }
+
+    // the same red quad written out in binary of either endianness
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = match big_endian {
+            true => "binary_big_endian",
+            false => "binary_little_endian",
+        };
+        let mut bytes = header(format).into_bytes();
+        for corner in CORNERS {
+            for coordinate in corner {
+                bytes.extend(match big_endian {
+                    true => coordinate.to_be_bytes(),
+                    false => coordinate.to_le_bytes(),
+                });
+            }
+            bytes.extend(RED);
+        }
+        bytes.push(4);
+        for index in 0..4i32 {
+            bytes.extend(match big_endian {
+                true => index.to_be_bytes(),
+                false => index.to_le_bytes(),
+            });
+        }
+        bytes
+    }
+
+    fn assert_red_quad(mesh: &Mesh) {
+        assert_eq!(mesh.triangles.len(), 2);
+        let red = SolidSource::from_unpremultiplied_argb(0xff, 255, 0, 0);
+        for (triangle, corners) in mesh.triangles.iter().zip([[0, 1, 2], [0, 2, 3]]) {
+            for (vertex, corner) in triangle.vertices.iter().zip(corners) {
+                let [x, y, z] = CORNERS[corner];
+                assert_eq!(*vertex, Vector4::new(x, y, z, 1.));
+            }
+            assert_eq!(triangle.vertex_colors, Some([red; 3]));
+        }
+        assert!(!mesh.triangles[0].fan);
+        assert!(mesh.triangles[1].fan);
+    }
+
+    #[test]
+    fn ascii_and_both_binaries_read_the_same() {
+        let ascii = header("ascii")
+            + "0 0 0 255 0 0\n1 0 0 255 0 0\n1 1 0 255 0 0\n0 1 0 255 0 0\n4 0 1 2 3\n";
+        assert_red_quad(&parse_ply(ascii.as_bytes()).unwrap());
+        assert_red_quad(&parse_ply(&binary(false)).unwrap());
+        assert_red_quad(&parse_ply(&binary(true)).unwrap());
+    }
+
+    #[test]
+    fn short_or_out_of_range_bodies_are_refused() {
+        let bytes = binary(false);
+        assert!(parse_ply(&bytes[..bytes.len() - 1]).is_err());
+
+        let ascii = header("ascii")
+            + "0 0 0 255 0 0\n1 0 0 255 0 0\n1 1 0 255 0 0\n0 1 0 255 0 0\n4 0 1 2 9\n";
+        assert!(parse_ply(ascii.as_bytes()).is_err());
+        assert!(parse_ply(b"obj\nend_header\n").is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::{HashMap, VecDeque};
+
+use nalgebra::base::{Matrix4, Vector4};
+
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::{derive_normal, Triangle};
+use crate::linear_algebra::vectors::{
+    add_vec, cross_product, dot_product, mult_vec, normalize_vec, position_key, sub_vec,
+    vec_magnitude,
+};
+
+// twice a triangle's area over its longest edge squared, below this it's a sliver
+// too thin to ever cover a pixel
+const DEGENERATE_RATIO: f32 = 1e-6;
+
+// two corner positions, by key
+type Edge = ([u32; 3], [u32; 3]);
+
+// cleanup for imported meshes. apart from weld they all match corners by exact
+// position, so weld first when a file repeats positions with rounding noise
+impl Mesh {
+    // snaps each vertex onto the nearest one already kept within `tolerance`,
+    // so shared corners written with slightly different floats become one position
+    pub fn weld(&mut self, tolerance: f32) {
+        if tolerance <= 0. {
+            return;
+        }
+        // cells twice the tolerance across, so anything within it is at most one
+        // cell over, toward whichever side of its own cell the vertex is on
+        let size = tolerance * 2.;
+        let cell = |v: &Vector4<f32>| {
+            [v.x, v.y, v.z].map(|value| {
+                let scaled = value / size;
+                let cell = scaled.floor();
+                let side = if scaled - cell < 0.5 { -1 } else { 1 };
+                (cell as i64, side)
+            })
+        };
+        let mut kept: HashMap<[i64; 3], Vec<Vector4<f32>>> = HashMap::new();
+        // where each exact position went, most corners repeat one already seen
+        let mut welded: HashMap<[u32; 3], Vector4<f32>> = HashMap::new();
+
+        for triangle in self.triangles.iter_mut() {
+            for vertex in triangle.vertices.iter_mut() {
+                let exact = position_key(vertex);
+                if let Some(position) = welded.get(&exact) {
+                    *vertex = *position;
+                    continue;
+                }
+
+                let [(x, side_x), (y, side_y), (z, side_z)] = cell(vertex);
+                let mut nearest: Option<(f32, Vector4<f32>)> = None;
+                for offset in 0..8 {
+                    let neighbour = [
+                        x + side_x * (offset & 1),
+                        y + side_y * (offset >> 1 & 1),
+                        z + side_z * (offset >> 2),
+                    ];
+                    for candidate in kept.get(&neighbour).into_iter().flatten() {
+                        let distance = vec_magnitude(&sub_vec(candidate, vertex));
+                        if distance <= tolerance && nearest.is_none_or(|(d, _)| distance < d) {
+                            nearest = Some((distance, *candidate));
+                        }
+                    }
+                }
+
+                match nearest {
+                    Some((_, candidate)) => *vertex = candidate,
+                    None => kept.entry([x, y, z]).or_default().push(*vertex),
+                }
+                welded.insert(exact, *vertex);
+            }
+        }
+    }
+
+    // corner normals averaged over the faces around each position, weighted by the
+    // angle each face makes there. faces meeting at more than `crease_angle` degrees
+    // don't share, keeping that edge sharp. 0 or less clears them, back to flat faces
+    pub fn recompute_normals(&mut self, crease_angle: f32) {
+        if crease_angle <= 0. {
+            for triangle in self.triangles.iter_mut() {
+                triangle.normals = None;
+            }
+            return;
+        }
+        let limit = crease_angle.min(180.).to_radians().cos();
+
+        let faces: Vec<Vector4<f32>> = self.triangles.iter().map(derive_normal).collect();
+        let mut corners: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            for (c, vertex) in triangle.vertices.iter().enumerate() {
+                corners
+                    .entry(position_key(vertex))
+                    .or_default()
+                    .push((t, c));
+            }
+        }
+
+        let mut normals: Vec<[Vector4<f32>; 3]> = faces.iter().map(|face| [*face; 3]).collect();
+        for around in corners.values() {
+            for &(t, c) in around.iter() {
+                let sum = around
+                    .iter()
+                    .filter(|&&(u, _)| faces[u].x.is_finite())
+                    .filter(|&&(u, _)| {
+                        !faces[t].x.is_finite() || dot_product(&faces[t], &faces[u]) >= limit
+                    })
+                    .fold(Vector4::new(0., 0., 0., 1.), |sum, &(u, d)| {
+                        let angle = corner_angle(&self.triangles[u], d);
+                        add_vec(&sum, &mult_vec(&faces[u], angle))
+                    });
+                if vec_magnitude(&sum) > 0. {
+                    normals[t][c] = normalize_vec(&sum);
+                }
+            }
+        }
+
+        for (triangle, normals) in self.triangles.iter_mut().zip(normals) {
+            triangle.normals = Some(normals);
+        }
+    }
+
+    // turns every face inside out
+    pub fn flip_winding(&mut self) {
+        for triangle in self.triangles.iter_mut() {
+            flip(triangle);
+        }
+    }
+
+    // makes faces that share an edge wind the same way, so a few inverted faces
+    // stop being culled, then turns each connected piece whose faces point inward
+    // (by its signed volume) outward. returns how many faces were flipped
+    pub fn unify_winding(&mut self) -> usize {
+        // faces by edge, each with whether it runs the edge from its lower key up
+        let mut edges: HashMap<Edge, Vec<(usize, bool)>> = HashMap::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            for (a, b) in triangle_edges(triangle) {
+                if a != b {
+                    edges
+                        .entry((a.min(b), a.max(b)))
+                        .or_default()
+                        .push((t, a < b));
+                }
+            }
+        }
+
+        let mut flipped: Vec<Option<bool>> = vec![None; self.triangles.len()];
+        for start in 0..self.triangles.len() {
+            if flipped[start].is_some() {
+                continue;
+            }
+
+            // neighbours run a shared edge the other way once both are oriented
+            let mut piece: Vec<usize> = Vec::new();
+            let mut queue: VecDeque<usize> = VecDeque::from([start]);
+            flipped[start] = Some(false);
+            while let Some(t) = queue.pop_front() {
+                piece.push(t);
+                let flip_t = flipped[t].unwrap_or(false);
+                for (a, b) in triangle_edges(&self.triangles[t]) {
+                    let upward = (a < b) != flip_t;
+                    for &(u, u_upward) in edges.get(&(a.min(b), a.max(b))).into_iter().flatten() {
+                        if flipped[u].is_none() {
+                            flipped[u] = Some(u_upward == upward);
+                            queue.push_back(u);
+                        }
+                    }
+                }
+            }
+
+            // measured from the piece's own middle, which only matters if it's open
+            let middle = piece
+                .iter()
+                .flat_map(|&t| self.triangles[t].vertices.iter())
+                .fold(Vector4::new(0., 0., 0., 1.), |sum, v| add_vec(&sum, v));
+            let middle = mult_vec(&middle, 1. / (piece.len() * 3) as f32);
+            let volume: f32 = piece
+                .iter()
+                .map(|&t| {
+                    let [a, mut b, mut c] =
+                        self.triangles[t].vertices.map(|v| sub_vec(&v, &middle));
+                    if flipped[t] == Some(true) {
+                        std::mem::swap(&mut b, &mut c);
+                    }
+                    dot_product(&a, &cross_product(&b, &c))
+                })
+                .sum();
+            if volume < 0. {
+                for &t in piece.iter() {
+                    flipped[t] = flipped[t].map(|flip| !flip);
+                }
+            }
+        }
+
+        let mut count = 0;
+        for (triangle, flip_it) in self.triangles.iter_mut().zip(flipped) {
+            if flip_it == Some(true) {
+                flip(triangle);
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    // moves the middle of the bounding box onto the origin, returning where it was
+    pub fn center(&mut self) -> Vector4<f32> {
+        let (min, max) = match self.bounds(&Matrix4::identity()) {
+            Some(bounds) => bounds,
+            None => return Vector4::new(0., 0., 0., 1.),
+        };
+        let middle = mult_vec(&add_vec(&min, &max), 0.5);
+
+        for triangle in self.triangles.iter_mut() {
+            for vertex in triangle.vertices.iter_mut() {
+                *vertex = sub_vec(vertex, &middle);
+            }
+        }
+
+        middle
+    }
+
+    // scales about the middle of the bounding box until its longest side is `size`,
+    // 1 for unit bounds. returns the factor, 1 when there's nothing to scale
+    pub fn scale_to_size(&mut self, size: f32) -> f32 {
+        let (min, max) = match self.bounds(&Matrix4::identity()) {
+            Some(bounds) => bounds,
+            None => return 1.,
+        };
+        let extent = sub_vec(&max, &min);
+        let longest = extent.x.max(extent.y).max(extent.z);
+        if longest <= 0. || !longest.is_finite() {
+            return 1.;
+        }
+        let middle = mult_vec(&add_vec(&min, &max), 0.5);
+
+        for triangle in self.triangles.iter_mut() {
+            for vertex in triangle.vertices.iter_mut() {
+                *vertex = add_vec(
+                    &middle,
+                    &mult_vec(&sub_vec(vertex, &middle), size / longest),
+                );
+            }
+        }
+
+        size / longest
+    }
+
+    // drops faces with no area to speak of, returns how many went
+    pub fn remove_degenerate(&mut self) -> usize {
+        let before = self.triangles.len();
+        self.triangles.retain(|triangle| {
+            let [a, b, c] = &triangle.vertices;
+            let (ab, ac, bc) = (sub_vec(b, a), sub_vec(c, a), sub_vec(c, b));
+            let longest = dot_product(&ab, &ab)
+                .max(dot_product(&ac, &ac))
+                .max(dot_product(&bc, &bc));
+
+            vec_magnitude(&cross_product(&ab, &ac)) > DEGENERATE_RATIO * longest
+        });
+
+        before - self.triangles.len()
+    }
+}
+
+fn flip(triangle: &mut Triangle) {
+    triangle.vertices.swap(1, 2);
+    if let Some(colors) = triangle.vertex_colors.as_mut() {
+        colors.swap(1, 2);
+    }
+    if let Some(uvs) = triangle.uvs.as_mut() {
+        uvs.swap(1, 2);
+    }
+    if let Some(normals) = triangle.normals.as_mut() {
+        normals.swap(1, 2);
+        *normals = normals.map(|n| mult_vec(&n, -1.));
+    }
+}
+
+// the three edges in winding order, by position
+fn triangle_edges(triangle: &Triangle) -> [Edge; 3] {
+    let keys = triangle.vertices.map(|v| position_key(&v));
+    [(keys[0], keys[1]), (keys[1], keys[2]), (keys[2], keys[0])]
+}
+
+// angle in radians between the two edges leaving `corner`
+fn corner_angle(triangle: &Triangle, corner: usize) -> f32 {
+    let vertex = &triangle.vertices[corner];
+    let to = |i: usize| sub_vec(&triangle.vertices[(corner + i) % 3], vertex);
+    let (a, b) = (to(1), to(2));
+    let lengths = vec_magnitude(&a) * vec_magnitude(&b);
+    if lengths <= 0. {
+        return 0.;
+    }
+
+    (dot_product(&a, &b) / lengths).clamp(-1., 1.).acos()
+}
@@ -0,0 +1,470 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use nalgebra::base::{Matrix3, Vector3, Vector4};
+
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::Triangle;
+use crate::linear_algebra::vectors::{
+    add_vec, cross_product, dot_product, mult_vec, normalize_vec, position_key, sub_vec,
+    vec_magnitude,
+};
+
+// open edges are held in place this many times harder than surfaces, or holes
+// and sheet edges would be the first thing to go
+const BOUNDARY_WEIGHT: f64 = 100.;
+
+impl Mesh {
+    // collapses edges, cheapest first by quadric error, until at most `target`
+    // triangles are left or the next collapse would cost more than `max_error`.
+    // returns the error reached, see Decimator::error
+    pub fn decimate(&mut self, target: usize, max_error: f32) -> f32 {
+        let mut decimator = Decimator::new(self);
+        let error = decimator.collapse_to(target, max_error);
+        let decimated = decimator.mesh();
+        *self = decimated;
+
+        error
+    }
+}
+
+// quadric error metric edge collapse (garland and heckbert). corners are joined
+// by exact position, so weld first if a file repeats them with rounding noise.
+// collapses can be run in steps, taking a snapshot of the mesh after each, with
+// every error still measured against the original surface
+pub struct Decimator<'a> {
+    // where the faces' colours, uvs and normals come from
+    source: &'a Mesh,
+    positions: Vec<Vector4<f32>>,
+    quadrics: Vec<Quadric>,
+    // bumped whenever a vertex moves, so queued collapses can tell they're stale
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+    // one per source triangle, None once it's collapsed away or if it never had area
+    faces: Vec<Option<[usize; 3]>>,
+    // may still list faces that are gone, they're skipped
+    vertex_faces: Vec<Vec<usize>>,
+    queue: BinaryHeap<Collapse>,
+    live: usize,
+    error: f64,
+}
+
+impl<'a> Decimator<'a> {
+    pub fn new(source: &'a Mesh) -> Decimator<'a> {
+        let mut ids: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions: Vec<Vector4<f32>> = Vec::new();
+        let faces: Vec<Option<[usize; 3]>> = source
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let face = triangle.vertices.map(|v| {
+                    *ids.entry(position_key(&v)).or_insert_with(|| {
+                        positions.push(v);
+                        positions.len() - 1
+                    })
+                });
+                let [a, b, c] = face;
+                (a != b && b != c && c != a).then_some(face)
+            })
+            .collect();
+
+        let mut decimator = Decimator {
+            source,
+            quadrics: vec![Quadric::default(); positions.len()],
+            versions: vec![0; positions.len()],
+            removed: vec![false; positions.len()],
+            vertex_faces: vec![Vec::new(); positions.len()],
+            positions,
+            live: faces.iter().flatten().count(),
+            faces,
+            queue: BinaryHeap::new(),
+            error: 0.,
+        };
+
+        // every face's plane goes to its corners, counting how often each edge is used
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (f, face) in decimator.faces.iter().enumerate() {
+            let face = match face {
+                Some(face) => face,
+                None => continue,
+            };
+            let normal = decimator.face_normal(face);
+            if normal.x.is_finite() {
+                let plane = Quadric::plane(&normal, &decimator.positions[face[0]], 1.);
+                for &v in face.iter() {
+                    decimator.quadrics[v].add(&plane);
+                }
+            }
+            for i in 0..3 {
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                edges.entry((a.min(b), a.max(b))).or_default().push(f);
+                decimator.vertex_faces[face[i]].push(f);
+            }
+        }
+
+        // an edge with one face gets a plane standing up along it, keeping it where it is
+        for (&(a, b), around) in edges.iter() {
+            if let [f] = around[..] {
+                let face = decimator.faces[f].unwrap();
+                let along = sub_vec(&decimator.positions[b], &decimator.positions[a]);
+                let normal = normalize_vec(&cross_product(&along, &decimator.face_normal(&face)));
+                if normal.x.is_finite() {
+                    let plane = Quadric::plane(&normal, &decimator.positions[a], BOUNDARY_WEIGHT);
+                    decimator.quadrics[a].add(&plane);
+                    decimator.quadrics[b].add(&plane);
+                }
+            }
+        }
+
+        for &(a, b) in edges.keys() {
+            decimator.queue_collapse(a, b);
+        }
+
+        decimator
+    }
+
+    // collapses until at most `target` faces are left or the cheapest collapse
+    // costs more than `max_error`. returns the error reached
+    pub fn collapse_to(&mut self, target: usize, max_error: f32) -> f32 {
+        let limit = (max_error as f64).powi(2);
+
+        while self.live > target {
+            let collapse = match self.queue.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let (a, b) = (collapse.a, collapse.b);
+            if self.removed[a]
+                || self.removed[b]
+                || self.versions[a] != collapse.versions.0
+                || self.versions[b] != collapse.versions.1
+            {
+                continue;
+            }
+            if collapse.cost > limit {
+                self.queue.push(collapse);
+                break;
+            }
+            // one that would tear or fold the surface may be fine once the
+            // area around it has changed, it's queued again when either end moves
+            if !self.keeps_manifold(a, b) || self.folds(a, b, &collapse.position) {
+                continue;
+            }
+
+            self.collapse(&collapse);
+        }
+
+        self.error()
+    }
+
+    // how far the surface may have moved from any of the original faces' planes,
+    // in model units. open edges count BOUNDARY_WEIGHT times over
+    pub fn error(&self) -> f32 {
+        self.error.sqrt() as f32
+    }
+
+    // faces left right now
+    pub fn faces_left(&self) -> usize {
+        self.live
+    }
+
+    // the mesh as collapsed so far. faces keep their own colours, uvs and normals,
+    // just with their corners moved, but no longer make up polygons
+    pub fn mesh(&self) -> Mesh {
+        let triangles = self
+            .faces
+            .iter()
+            .zip(self.source.triangles.iter())
+            .filter_map(|(face, triangle)| {
+                let face = (*face)?;
+                Some(Triangle {
+                    vertices: face.map(|v| self.positions[v]),
+                    fan: false,
+                    ..triangle.clone()
+                })
+            })
+            .collect();
+
+        Mesh { triangles }
+    }
+
+    // b goes into a, which moves to where the collapse put it
+    fn collapse(&mut self, collapse: &Collapse) {
+        let (a, b) = (collapse.a, collapse.b);
+        self.positions[a] = collapse.position;
+        let merged = self.quadrics[b];
+        self.quadrics[a].add(&merged);
+        self.versions[a] += 1;
+        self.removed[b] = true;
+        self.error = self.error.max(collapse.cost);
+
+        for f in std::mem::take(&mut self.vertex_faces[b]) {
+            let face = match self.faces[f].as_mut() {
+                Some(face) => face,
+                None => continue,
+            };
+            match face.contains(&a) {
+                true => {
+                    self.faces[f] = None;
+                    self.live -= 1;
+                }
+                false => {
+                    for v in face.iter_mut().filter(|v| **v == b) {
+                        *v = a;
+                    }
+                    self.vertex_faces[a].push(f);
+                }
+            }
+        }
+        let faces = &self.faces;
+        self.vertex_faces[a].retain(|&f| faces[f].is_some());
+
+        for n in self.neighbours(a) {
+            self.queue_collapse(a, n);
+        }
+    }
+
+    // the cheapest place for a and b to meet: where their summed quadric is
+    // smallest if that can be solved for and stays near the edge, otherwise
+    // whichever of the two ends or the middle is cheapest
+    fn queue_collapse(&mut self, a: usize, b: usize) {
+        let mut quadric = self.quadrics[a];
+        quadric.add(&self.quadrics[b]);
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+        let middle = mult_vec(&add_vec(&pa, &pb), 0.5);
+        let length = vec_magnitude(&sub_vec(&pb, &pa));
+
+        let mut candidates = vec![pa, pb, middle];
+        if let Some(best) = quadric.minimum() {
+            if vec_magnitude(&sub_vec(&best, &middle)) <= length {
+                candidates.push(best);
+            }
+        }
+        let (cost, position) = candidates
+            .into_iter()
+            .map(|p| (quadric.error(&p).max(0.), p))
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+            .unwrap();
+
+        self.queue.push(Collapse {
+            cost,
+            a,
+            b,
+            versions: (self.versions[a], self.versions[b]),
+            position,
+        });
+    }
+
+    // the link condition: a and b may only share the neighbours across the faces
+    // on their edge, or the collapse pinches the surface
+    fn keeps_manifold(&self, a: usize, b: usize) -> bool {
+        let shared_faces = self.live_faces(a).filter(|face| face.contains(&b)).count();
+        let around_b: HashSet<usize> = self.neighbours(b).into_iter().collect();
+        let shared = self
+            .neighbours(a)
+            .into_iter()
+            .filter(|n| around_b.contains(n))
+            .count();
+
+        shared == shared_faces
+    }
+
+    // whether moving a and b to `position` turns any face that stays over, or
+    // squashes it flat
+    fn folds(&self, a: usize, b: usize, position: &Vector4<f32>) -> bool {
+        self.live_faces(a)
+            .chain(self.live_faces(b))
+            .filter(|face| !(face.contains(&a) && face.contains(&b)))
+            .any(|face| {
+                let before = self.face_normal(&face);
+                let moved = face.map(|v| match v == a || v == b {
+                    true => *position,
+                    false => self.positions[v],
+                });
+                let after = cross_product(
+                    &sub_vec(&moved[1], &moved[0]),
+                    &sub_vec(&moved[2], &moved[0]),
+                );
+                // nan, from a face already without area, counts as folding
+                dot_product(&before, &after).partial_cmp(&0.) != Some(Ordering::Greater)
+            })
+    }
+
+    fn live_faces(&self, v: usize) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.vertex_faces[v].iter().filter_map(|&f| self.faces[f])
+    }
+
+    fn neighbours(&self, v: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.live_faces(v).flatten().filter(|&n| n != v).collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+
+        neighbours
+    }
+
+    fn face_normal(&self, face: &[usize; 3]) -> Vector4<f32> {
+        let [a, b, c] = face.map(|v| self.positions[v]);
+
+        normalize_vec(&cross_product(&sub_vec(&b, &a), &sub_vec(&c, &a)))
+    }
+}
+
+// sum of squared distances to a set of planes, as the symmetric 4x4 matrix
+// [a b c d]^T [a b c d] summed over planes ax + by + cz + d = 0, upper triangle only
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(normal: &Vector4<f32>, point: &Vector4<f32>, weight: f64) -> Quadric {
+        let [a, b, c] = [normal.x, normal.y, normal.z].map(|n| n as f64);
+        let d = -dot_product(normal, point) as f64;
+
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|q| q * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (q, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *q += o;
+        }
+    }
+
+    fn error(&self, p: &Vector4<f32>) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let (x, y, z) = (p.x as f64, p.y as f64, p.z as f64);
+
+        aa * x * x
+            + 2. * ab * x * y
+            + 2. * ac * x * z
+            + 2. * ad * x
+            + bb * y * y
+            + 2. * bc * y * z
+            + 2. * bd * y
+            + cc * z * z
+            + 2. * cd * z
+            + dd
+    }
+
+    // the point with the least error, None where that's a line or plane of them
+    fn minimum(&self) -> Option<Vector4<f32>> {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, _] = self.0;
+        let m = Matrix3::new(aa, ab, ac, ab, bb, bc, ac, bc, cc);
+        // relative to the matrix's size, so small models aren't treated as singular
+        let scale = (aa + bb + cc) / 3.;
+        if m.determinant().abs() <= 1e-9 * scale.powi(3) {
+            return None;
+        }
+        let p = m.try_inverse()? * -Vector3::new(ad, bd, cd);
+
+        p.iter()
+            .all(|v| v.is_finite())
+            .then(|| Vector4::new(p.x as f32, p.y as f32, p.z as f32, 1.))
+    }
+}
+
+struct Collapse {
+    cost: f64,
+    a: usize,
+    b: usize,
+    versions: (u32, u32),
+    position: Vector4<f32>,
+}
+
+// cheapest first out of the max heap
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meshes::cube::_get_cube_mesh;
+
+    // every edge as its two corners by position, with the faces that use it
+    fn edge_counts(mesh: &Mesh) -> HashMap<([u32; 3], [u32; 3]), usize> {
+        let mut counts = HashMap::new();
+        for triangle in mesh.triangles.iter() {
+            for i in 0..3 {
+                let (a, b) = (
+                    position_key(&triangle.vertices[i]),
+                    position_key(&triangle.vertices[(i + 1) % 3]),
+                );
+                *counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn decimating_a_closed_mesh_keeps_it_closed() {
+        let mut mesh = _get_cube_mesh();
+        mesh.subdivide_loop(2, 0.);
+        let before = mesh.triangles.len();
+
+        let error = mesh.decimate(before / 4, f32::INFINITY);
+
+        assert!(mesh.triangles.len() <= before / 4);
+        assert!(error.is_finite());
+        assert!(edge_counts(&mesh).values().all(|&faces| faces == 2));
+    }
+
+    #[test]
+    fn a_flat_grid_collapses_without_error() {
+        let corner = |x: usize, y: usize| Vector4::new(x as f32, y as f32, 0., 1.);
+        let mut triangles: Vec<Triangle> = Vec::new();
+        for x in 0..8 {
+            for y in 0..8 {
+                for vertices in [
+                    [corner(x, y), corner(x + 1, y), corner(x + 1, y + 1)],
+                    [corner(x, y), corner(x + 1, y + 1), corner(x, y + 1)],
+                ] {
+                    triangles.push(Triangle {
+                        vertices,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        let mut mesh = Mesh { triangles };
+        let bounds = mesh.bounds(&nalgebra::Matrix4::identity());
+
+        let error = mesh.decimate(0, 1e-4);
+
+        assert!(mesh.triangles.len() < 16, "{} left", mesh.triangles.len());
+        assert!(error <= 1e-4);
+        assert!(mesh
+            .triangles
+            .iter()
+            .all(|t| t.vertices.iter().all(|v| v.z == 0.)));
+        assert_eq!(mesh.bounds(&nalgebra::Matrix4::identity()), bounds);
+    }
+}
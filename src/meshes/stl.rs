@@ -0,0 +1,184 @@
+use std::str::SplitWhitespace;
+
+use nalgebra::Vector4;
+
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::{derive_normal, Triangle};
+use crate::linear_algebra::vectors::dot_product;
+
+// 80 byte header then a u32 facet count
+const HEADER_SIZE: usize = 84;
+// normal, three vertices and a u16 attribute count
+const FACET_SIZE: usize = 50;
+
+// ascii or binary, told apart by the file size rather than the "solid" keyword,
+// plenty of binary exporters start their header with it too. some pad binary
+// files past their last facet, so one that's longer than its count needs is
+// binary unless the whole thing reads as ascii facets.
+// every facet repeats its corners, Mesh::weld can join them back up
+pub fn parse_stl(bytes: &[u8]) -> Result<Mesh, String> {
+    let text = || String::from_utf8_lossy(bytes);
+
+    match binary_facets(bytes) {
+        Some(count) if bytes.len() == HEADER_SIZE + count * FACET_SIZE => {
+            Ok(parse_binary(bytes, count))
+        }
+        Some(count) => match parse_ascii(&text()) {
+            Ok(mesh) if !mesh.triangles.is_empty() => Ok(mesh),
+            _ => Ok(parse_binary(bytes, count)),
+        },
+        None => parse_ascii(&text()),
+    }
+}
+
+// the facet count in the header, if the file is long enough to hold that many
+fn binary_facets(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < HEADER_SIZE {
+        return None;
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+
+    (bytes.len() >= HEADER_SIZE + count * FACET_SIZE).then_some(count)
+}
+
+fn parse_binary(bytes: &[u8], count: usize) -> Mesh {
+    let mut triangles: Vec<Triangle> = Vec::with_capacity(count);
+    let facets = &bytes[HEADER_SIZE..HEADER_SIZE + count * FACET_SIZE];
+
+    for facet in facets.chunks_exact(FACET_SIZE) {
+        let float = |i: usize| {
+            f32::from_le_bytes([
+                facet[i * 4],
+                facet[i * 4 + 1],
+                facet[i * 4 + 2],
+                facet[i * 4 + 3],
+            ])
+        };
+        let point = |i: usize| Vector4::new(float(i), float(i + 1), float(i + 2), 1.);
+
+        triangles.push(facet_triangle(point(0), [point(3), point(6), point(9)]));
+    }
+
+    Mesh { triangles }
+}
+
+fn parse_ascii(text: &str) -> Result<Mesh, String> {
+    let mut triangles: Vec<Triangle> = Vec::new();
+    let mut normal: Vector4<f32> = Vector4::new(0., 0., 0., 1.);
+    let mut vertices: Vec<Vector4<f32>> = Vec::with_capacity(3);
+
+    for (number, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("facet") => {
+                // skips "normal"
+                tokens.next();
+                normal = read_point(&mut tokens, number)?;
+                vertices.clear();
+            }
+            Some("vertex") => {
+                vertices.push(read_point(&mut tokens, number)?);
+            }
+            Some("endfacet") => {
+                if vertices.len() != 3 {
+                    return Err(format!(
+                        "line {}: facet has {} vertices, expected 3",
+                        number + 1,
+                        vertices.len()
+                    ));
+                }
+                triangles.push(facet_triangle(
+                    normal,
+                    [vertices[0], vertices[1], vertices[2]],
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    if triangles.is_empty() && !text.trim_start().starts_with("solid") {
+        return Err(String::from("neither ascii nor binary stl"));
+    }
+
+    Ok(Mesh { triangles })
+}
+
+fn read_point(tokens: &mut SplitWhitespace, number: usize) -> Result<Vector4<f32>, String> {
+    let mut next = || {
+        tokens
+            .next()
+            .and_then(|token| token.parse::<f32>().ok())
+            .ok_or(format!("line {}: expected three numbers", number + 1))
+    };
+
+    Ok(Vector4::new(next()?, next()?, next()?, 1.))
+}
+
+// the stored normal is only used to fix the winding, a zero normal leaves it alone
+fn facet_triangle(normal: Vector4<f32>, vertices: [Vector4<f32>; 3]) -> Triangle {
+    let mut triangle = Triangle {
+        vertices,
+        ..Default::default()
+    };
+    if dot_product(&normal, &derive_normal(&triangle)) < 0. {
+        triangle.vertices.swap(1, 2);
+    }
+
+    triangle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "solid tri
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid tri
+";
+
+    // one facet, its header starting with "solid" the way some exporters write it
+    fn binary(padding: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; HEADER_SIZE];
+        bytes[..11].copy_from_slice(b"solid fakes");
+        bytes[80..84].copy_from_slice(&1u32.to_le_bytes());
+        let floats: [f32; 12] = [0., 0., 1., 0., 0., 0., 1., 0., 0., 0., 1., 0.];
+        for float in floats {
+            bytes.extend(float.to_le_bytes());
+        }
+        bytes.extend([0, 0]);
+        bytes.extend(vec![0u8; padding]);
+
+        bytes
+    }
+
+    #[test]
+    fn ascii_and_binary_are_told_apart_by_size() {
+        let ascii = parse_stl(ASCII.as_bytes()).unwrap();
+        assert_eq!(ascii.triangles.len(), 1);
+        assert_eq!(ascii.triangles[0].vertices[1], Vector4::new(1., 0., 0., 1.));
+
+        for padding in [0, 1, 64] {
+            let mesh = parse_stl(&binary(padding)).unwrap();
+            assert_eq!(mesh.triangles.len(), 1, "padded by {}", padding);
+            assert_eq!(mesh.triangles[0].vertices[1], Vector4::new(1., 0., 0., 1.));
+        }
+
+        assert!(parse_stl(b"not a model").is_err());
+    }
+
+    // the stored normal points the other way, so the corners are swapped to match
+    #[test]
+    fn facets_are_wound_to_their_normal() {
+        let flipped = ASCII.replace("normal 0 0 1", "normal 0 0 -1");
+        let mesh = parse_stl(flipped.as_bytes()).unwrap();
+
+        assert_eq!(mesh.triangles[0].vertices[1], Vector4::new(0., 1., 0., 1.));
+    }
+}
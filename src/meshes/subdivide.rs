@@ -0,0 +1,514 @@
+use std::collections::{HashMap, HashSet};
+
+use nalgebra::base::Vector4;
+use raqote::SolidSource;
+
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::Triangle;
+use crate::linear_algebra::vectors::{
+    add_vec, cross_product, dot_product, mult_vec, normalize_vec, position_key,
+};
+
+// smooth subdivision. edges where faces meet at more than `crease_angle` degrees
+// stay sharp, as do open edges, so hard edges and boundaries keep their lines
+// (0 keeps everything sharp and only splits the faces). uvs and vertex colours
+// are blended linearly across each face, which leaves seams where they were.
+// corner normals don't survive, recompute them after
+impl Mesh {
+    // catmull-clark for a mesh with polygons of more than three corners, like obj
+    // and ply n-gons load as, loop for one that's all triangles
+    pub fn subdivide(&mut self, levels: usize, crease_angle: f32) {
+        match self.triangles.iter().any(|triangle| triangle.fan) {
+            true => self.subdivide_catmull_clark(levels, crease_angle),
+            false => self.subdivide_loop(levels, crease_angle),
+        }
+    }
+
+    // triangles subdivide(levels) will leave, without doing it. a polygon's first
+    // catmull-clark step makes a quad per corner, so fans grow faster than four times
+    pub fn subdivided_len(&self, levels: usize) -> usize {
+        if levels == 0 {
+            return self.triangles.len();
+        }
+
+        match self.triangles.iter().any(|triangle| triangle.fan) {
+            true => {
+                // a polygon of n corners is a fan of n - 2 triangles
+                let polygons = self
+                    .triangles
+                    .iter()
+                    .filter(|triangle| !triangle.fan)
+                    .count();
+                let corners = self.triangles.len() + 2 * polygons;
+                (2 * corners) << (2 * (levels - 1))
+            }
+            false => self.triangles.len() << (2 * levels),
+        }
+    }
+
+    // every triangle into four a level, polygons are treated as their triangles
+    pub fn subdivide_loop(&mut self, levels: usize, crease_angle: f32) {
+        if levels == 0 {
+            return;
+        }
+        let mut surface = Surface::from_mesh(self, false);
+        for _ in 0..levels {
+            surface = surface.loop_step(crease_angle);
+        }
+
+        *self = surface.to_mesh();
+    }
+
+    // every polygon into one quad per corner a level, triangles included. the
+    // quads come out as fans too, so it can be run again later
+    pub fn subdivide_catmull_clark(&mut self, levels: usize, crease_angle: f32) {
+        if levels == 0 {
+            return;
+        }
+        let mut surface = Surface::from_mesh(self, true);
+        for _ in 0..levels {
+            surface = surface.catmull_clark_step(crease_angle);
+        }
+
+        *self = surface.to_mesh();
+    }
+}
+
+// what meets at each vertex, by index
+struct Around {
+    // other ends of every edge, and of the sharp ones
+    neighbours: Vec<Vec<usize>>,
+    sharp: Vec<Vec<usize>>,
+    faces: Vec<Vec<usize>>,
+}
+
+// faces sharing corners by index, which the schemes need to find neighbours
+struct Surface {
+    positions: Vec<Vector4<f32>>,
+    polygons: Vec<Polygon>,
+}
+
+struct Polygon {
+    vertices: Vec<usize>,
+    uvs: Option<Vec<[f32; 2]>>,
+    colors: Option<Vec<SolidSource>>,
+    // the triangle it came from, every piece keeps its colours
+    source: Triangle,
+}
+
+impl Surface {
+    // corners joined by exact position. with `join_fans` the triangles of a split
+    // polygon are put back into one. faces without area are left out
+    fn from_mesh(mesh: &Mesh, join_fans: bool) -> Surface {
+        let mut ids: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions: Vec<Vector4<f32>> = Vec::new();
+        let mut polygons: Vec<Polygon> = Vec::new();
+
+        for triangle in mesh.triangles.iter() {
+            let corners = triangle.vertices.map(|v| {
+                *ids.entry(position_key(&v)).or_insert_with(|| {
+                    positions.push(v);
+                    positions.len() - 1
+                })
+            });
+            let [a, b, c] = corners;
+            if a == b || b == c || c == a {
+                continue;
+            }
+
+            let joined = join_fans
+                && triangle.fan
+                && polygons
+                    .last_mut()
+                    .is_some_and(|polygon| polygon.extend(corners, triangle));
+            if !joined {
+                polygons.push(Polygon {
+                    vertices: corners.to_vec(),
+                    uvs: triangle.uvs.map(|uvs| uvs.to_vec()),
+                    colors: triangle.vertex_colors.map(|colors| colors.to_vec()),
+                    source: triangle.clone(),
+                });
+            }
+        }
+
+        Surface {
+            positions,
+            polygons,
+        }
+    }
+
+    // polygons split back into fans
+    fn to_mesh(&self) -> Mesh {
+        let mut triangles: Vec<Triangle> = Vec::new();
+        for polygon in self.polygons.iter() {
+            for i in 1..polygon.vertices.len() - 1 {
+                let corners = [0, i, i + 1];
+                triangles.push(Triangle {
+                    vertices: corners.map(|c| self.positions[polygon.vertices[c]]),
+                    uvs: polygon.uvs.as_ref().map(|uvs| corners.map(|c| uvs[c])),
+                    vertex_colors: polygon
+                        .colors
+                        .as_ref()
+                        .map(|colors| corners.map(|c| colors[c])),
+                    normals: None,
+                    fan: i > 1,
+                    ..polygon.source.clone()
+                });
+            }
+        }
+
+        Mesh { triangles }
+    }
+
+    fn loop_step(&self, crease_angle: f32) -> Surface {
+        let edges = self.edges();
+        let sharp = self.sharp_edges(&edges, crease_angle);
+        let around = self.around(&edges, &sharp);
+
+        // old vertices move toward their neighbours, by warren's weights
+        let mut positions: Vec<Vector4<f32>> = (0..self.positions.len())
+            .map(|v| {
+                self.sharp_vertex(v, &around).unwrap_or_else(|| {
+                    let n = around.neighbours[v].len();
+                    if n == 0 {
+                        return self.positions[v];
+                    }
+                    let beta = match n {
+                        3 => 3. / 16.,
+                        _ => 3. / (8. * n as f32),
+                    };
+                    let sum = self.sum(around.neighbours[v].iter());
+                    add_vec(
+                        &mult_vec(&self.positions[v], 1. - n as f32 * beta),
+                        &mult_vec(&sum, beta),
+                    )
+                })
+            })
+            .collect();
+
+        // a new vertex on every edge, pulled toward the corners across from it
+        let mut edge_points: HashMap<(usize, usize), usize> = HashMap::new();
+        for (&(a, b), faces) in edges.iter() {
+            let ends = self.sum([a, b].iter());
+            let point = match (sharp.contains(&(a, b)), &faces[..]) {
+                (false, &[f, g]) => {
+                    let across = [f, g].map(|f| self.polygons[f].across(a, b));
+                    add_vec(
+                        &mult_vec(&ends, 3. / 8.),
+                        &mult_vec(&self.sum(across.iter()), 1. / 8.),
+                    )
+                }
+                _ => mult_vec(&ends, 0.5),
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(point);
+        }
+
+        let mut polygons: Vec<Polygon> = Vec::new();
+        for polygon in self.polygons.iter() {
+            let corner = |i: usize| (polygon.vertices[i % 3], vec![i % 3]);
+            let middle = |i: usize| {
+                let (a, b) = (polygon.vertices[i % 3], polygon.vertices[(i + 1) % 3]);
+                (edge_points[&edge(a, b)], vec![i % 3, (i + 1) % 3])
+            };
+            for i in 0..3 {
+                polygons.push(polygon.piece(&[corner(i), middle(i), middle(i + 2)]));
+            }
+            polygons.push(polygon.piece(&[middle(0), middle(1), middle(2)]));
+        }
+
+        Surface {
+            positions,
+            polygons,
+        }
+    }
+
+    fn catmull_clark_step(&self, crease_angle: f32) -> Surface {
+        let edges = self.edges();
+        let sharp = self.sharp_edges(&edges, crease_angle);
+        let around = self.around(&edges, &sharp);
+
+        let face_points: Vec<Vector4<f32>> = self
+            .polygons
+            .iter()
+            .map(|polygon| self.average(polygon.vertices.iter()))
+            .collect();
+
+        // (faces + 2 edge middles + (n - 3) itself) / n, averaging around the vertex
+        let mut positions: Vec<Vector4<f32>> = (0..self.positions.len())
+            .map(|v| {
+                self.sharp_vertex(v, &around).unwrap_or_else(|| {
+                    let n = around.neighbours[v].len();
+                    if n < 3 || around.faces[v].is_empty() {
+                        return self.positions[v];
+                    }
+                    let faces = around.faces[v]
+                        .iter()
+                        .fold(Vector4::new(0., 0., 0., 1.), |sum, &f| {
+                            add_vec(&sum, &face_points[f])
+                        });
+                    let faces = mult_vec(&faces, 1. / around.faces[v].len() as f32);
+                    let middles = mult_vec(
+                        &add_vec(
+                            &self.average(around.neighbours[v].iter()),
+                            &self.positions[v],
+                        ),
+                        0.5,
+                    );
+                    let sum = add_vec(
+                        &add_vec(&faces, &mult_vec(&middles, 2.)),
+                        &mult_vec(&self.positions[v], n as f32 - 3.),
+                    );
+                    mult_vec(&sum, 1. / n as f32)
+                })
+            })
+            .collect();
+
+        let mut edge_points: HashMap<(usize, usize), usize> = HashMap::new();
+        for (&(a, b), faces) in edges.iter() {
+            let ends = self.sum([a, b].iter());
+            let point = match (sharp.contains(&(a, b)), &faces[..]) {
+                (false, &[f, g]) => mult_vec(
+                    &add_vec(&ends, &add_vec(&face_points[f], &face_points[g])),
+                    0.25,
+                ),
+                _ => mult_vec(&ends, 0.5),
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(point);
+        }
+
+        let mut polygons: Vec<Polygon> = Vec::new();
+        for (f, polygon) in self.polygons.iter().enumerate() {
+            let n = polygon.vertices.len();
+            let center = (positions.len(), (0..n).collect::<Vec<usize>>());
+            positions.push(face_points[f]);
+            let middle = |i: usize| {
+                let (a, b) = (polygon.vertices[i % n], polygon.vertices[(i + 1) % n]);
+                (edge_points[&edge(a, b)], vec![i % n, (i + 1) % n])
+            };
+            for i in 0..n {
+                polygons.push(polygon.piece(&[
+                    (polygon.vertices[i], vec![i]),
+                    middle(i),
+                    center.clone(),
+                    middle(i + n - 1),
+                ]));
+            }
+        }
+
+        Surface {
+            positions,
+            polygons,
+        }
+    }
+
+    // the polygons around each edge
+    fn edges(&self) -> HashMap<(usize, usize), Vec<usize>> {
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (f, polygon) in self.polygons.iter().enumerate() {
+            let n = polygon.vertices.len();
+            for i in 0..n {
+                let (a, b) = (polygon.vertices[i], polygon.vertices[(i + 1) % n]);
+                edges.entry(edge(a, b)).or_default().push(f);
+            }
+        }
+
+        edges
+    }
+
+    // open, shared by more than two faces, or creased past crease_angle
+    fn sharp_edges(
+        &self,
+        edges: &HashMap<(usize, usize), Vec<usize>>,
+        crease_angle: f32,
+    ) -> HashSet<(usize, usize)> {
+        let limit = crease_angle.min(180.).to_radians().cos();
+        let normals: Vec<Vector4<f32>> = self
+            .polygons
+            .iter()
+            .map(|polygon| self.normal(polygon))
+            .collect();
+
+        edges
+            .iter()
+            .filter(|(_, around)| match around[..] {
+                [f, g] => dot_product(&normals[f], &normals[g]) < limit,
+                _ => true,
+            })
+            .map(|(&edge, _)| edge)
+            .collect()
+    }
+
+    fn around(
+        &self,
+        edges: &HashMap<(usize, usize), Vec<usize>>,
+        sharp: &HashSet<(usize, usize)>,
+    ) -> Around {
+        let mut around = Around {
+            neighbours: vec![Vec::new(); self.positions.len()],
+            sharp: vec![Vec::new(); self.positions.len()],
+            faces: vec![Vec::new(); self.positions.len()],
+        };
+        for &(a, b) in edges.keys() {
+            around.neighbours[a].push(b);
+            around.neighbours[b].push(a);
+            if sharp.contains(&(a, b)) {
+                around.sharp[a].push(b);
+                around.sharp[b].push(a);
+            }
+        }
+        for (f, polygon) in self.polygons.iter().enumerate() {
+            for &v in polygon.vertices.iter() {
+                around.faces[v].push(f);
+            }
+        }
+
+        around
+    }
+
+    // the rules both schemes share: a vertex on a crease or boundary follows only
+    // the two sharp edges through it, one where more meet is a corner and stays,
+    // as does the corner of a lone face on a boundary. None for a smooth vertex,
+    // or the end of a crease, left to the scheme
+    fn sharp_vertex(&self, v: usize, around: &Around) -> Option<Vector4<f32>> {
+        match around.sharp[v][..] {
+            [] | [_] => None,
+            [_, _] if around.faces[v].len() == 1 => Some(self.positions[v]),
+            [a, b] => Some(add_vec(
+                &mult_vec(&self.positions[v], 3. / 4.),
+                &mult_vec(&self.sum([a, b].iter()), 1. / 8.),
+            )),
+            _ => Some(self.positions[v]),
+        }
+    }
+
+    // newell's, so polygons that aren't quite flat still get a fair normal
+    fn normal(&self, polygon: &Polygon) -> Vector4<f32> {
+        let n = polygon.vertices.len();
+        let sum = (0..n).fold(Vector4::new(0., 0., 0., 1.), |sum, i| {
+            let a = &self.positions[polygon.vertices[i]];
+            let b = &self.positions[polygon.vertices[(i + 1) % n]];
+            add_vec(&sum, &cross_product(a, b))
+        });
+
+        normalize_vec(&sum)
+    }
+
+    fn sum<'a>(&self, vertices: impl Iterator<Item = &'a usize>) -> Vector4<f32> {
+        vertices.fold(Vector4::new(0., 0., 0., 1.), |sum, &v| {
+            add_vec(&sum, &self.positions[v])
+        })
+    }
+
+    fn average<'a>(&self, vertices: impl ExactSizeIterator<Item = &'a usize>) -> Vector4<f32> {
+        let count = vertices.len().max(1) as f32;
+
+        mult_vec(&self.sum(vertices), 1. / count)
+    }
+}
+
+impl Polygon {
+    // takes in the next triangle of a fan, which either runs on from the last
+    // corner or, once the fan's been flipped, back from the second. false if it
+    // doesn't fit, say when the triangle before it was dropped
+    fn extend(&mut self, corners: [usize; 3], triangle: &Triangle) -> bool {
+        if self.vertices[0] != corners[0]
+            || self.uvs.is_some() != triangle.uvs.is_some()
+            || self.colors.is_some() != triangle.vertex_colors.is_some()
+        {
+            return false;
+        }
+        let (at, corner) = match self.vertices.last() == Some(&corners[1]) {
+            true => (self.vertices.len(), 2),
+            false if self.vertices[1] == corners[2] => (1, 1),
+            false => return false,
+        };
+        if self.vertices.contains(&corners[corner]) {
+            return false;
+        }
+
+        self.vertices.insert(at, corners[corner]);
+        if let (Some(uvs), Some(from)) = (self.uvs.as_mut(), triangle.uvs) {
+            uvs.insert(at, from[corner]);
+        }
+        if let (Some(colors), Some(from)) = (self.colors.as_mut(), triangle.vertex_colors) {
+            colors.insert(at, from[corner]);
+        }
+
+        true
+    }
+
+    // the corner of a triangle that isn't on the edge a b
+    fn across(&self, a: usize, b: usize) -> usize {
+        *self
+            .vertices
+            .iter()
+            .find(|&&v| v != a && v != b)
+            .unwrap_or(&a)
+    }
+
+    // part of this polygon. each corner is a new vertex, with the uv and colour
+    // averaged from the old corners listed beside it
+    fn piece(&self, corners: &[(usize, Vec<usize>)]) -> Polygon {
+        Polygon {
+            vertices: corners.iter().map(|(v, _)| *v).collect(),
+            uvs: self.uvs.as_ref().map(|uvs| {
+                corners
+                    .iter()
+                    .map(|(_, from)| {
+                        let sum = from
+                            .iter()
+                            .fold([0., 0.], |sum, &i| [sum[0] + uvs[i][0], sum[1] + uvs[i][1]]);
+                        sum.map(|s| s / from.len() as f32)
+                    })
+                    .collect()
+            }),
+            colors: self.colors.as_ref().map(|colors| {
+                corners
+                    .iter()
+                    .map(|(_, from)| {
+                        let channel = |get: fn(&SolidSource) -> u8| {
+                            let sum: u32 = from.iter().map(|&i| get(&colors[i]) as u32).sum();
+                            (sum / from.len() as u32) as u8
+                        };
+                        SolidSource {
+                            r: channel(|c| c.r),
+                            g: channel(|c| c.g),
+                            b: channel(|c| c.b),
+                            a: channel(|c| c.a),
+                        }
+                    })
+                    .collect()
+            }),
+            source: self.source.clone(),
+        }
+    }
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meshes::cube::_get_cube_mesh;
+
+    #[test]
+    fn subdivided_len_matches_subdivide() {
+        let triangles = _get_cube_mesh();
+        // the same cube as six quads, apart from the first face left as two triangles
+        let mut mixed = _get_cube_mesh();
+        for (i, triangle) in mixed.triangles.iter_mut().enumerate() {
+            triangle.fan = i % 2 == 1 && i > 1;
+        }
+
+        for mesh in [triangles, mixed] {
+            for levels in 0..3 {
+                let mut subdivided = mesh.clone();
+                subdivided.subdivide(levels, 0.);
+                assert_eq!(mesh.subdivided_len(levels), subdivided.triangles.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use nalgebra::Vector4;
+use raqote::SolidSource;
+
+use crate::drawing::colors::mix_colors;
+use crate::linear_algebra::data::Mesh;
+use crate::linear_algebra::triangles::{derive_normal, Triangle};
+use crate::meshes::load_error::LoadError;
+use crate::meshes::noise::{NoiseSettings, Perlin};
+
+pub enum TerrainColoring {
+    Elevation,
+    Slope,
+}
+
+pub struct TerrainSettings {
+    // width and depth of the terrain in world units
+    pub size: f32,
+    // number of quads along each side
+    pub resolution: usize,
+    // world height of a fully white pixel / noise peak
+    pub height_scale: f32,
+    pub coloring: TerrainColoring,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        TerrainSettings {
+            size: 20.,
+            resolution: 48,
+            height_scale: 4.,
+            coloring: TerrainColoring::Elevation,
+        }
+    }
+}
+
+pub fn get_terrain_from_noise(settings: &TerrainSettings, noise: &NoiseSettings) -> Mesh {
+    let perlin = Perlin::new(noise.seed);
+    let samples = settings.resolution + 1;
+    let step = settings.size / settings.resolution as f32;
+
+    let mut heights: Vec<f32> = Vec::with_capacity(samples * samples);
+    for row in 0..samples {
+        for col in 0..samples {
+            let value = perlin.fractal(col as f32 * step, row as f32 * step, noise);
+            // noise is -1..1, heights are 0..1
+            heights.push((value + 1.) * 0.5);
+        }
+    }
+
+    build_grid(&heights, samples, settings)
+}
+
+// grayscale heightmap: black is the lowest point, white the highest
+pub fn get_terrain_from_heightmap(
+    file_path: &str,
+    settings: &TerrainSettings,
+) -> Result<Mesh, LoadError> {
+    let image = image::open(file_path)?.to_luma8();
+    let (width, height) = image.dimensions();
+    let samples = settings.resolution + 1;
+
+    let pixel = |x: u32, y: u32| image.get_pixel(x.min(width - 1), y.min(height - 1)).0[0] as f32;
+
+    // bilinear resample so the grid resolution doesn't have to match the image
+    let mut heights: Vec<f32> = Vec::with_capacity(samples * samples);
+    for row in 0..samples {
+        for col in 0..samples {
+            let u = col as f32 / settings.resolution as f32 * (width - 1) as f32;
+            let v = row as f32 / settings.resolution as f32 * (height - 1) as f32;
+            let (x, y) = (u.floor() as u32, v.floor() as u32);
+            let (fx, fy) = (u.fract(), v.fract());
+
+            let top = pixel(x, y) * (1. - fx) + pixel(x + 1, y) * fx;
+            let bottom = pixel(x, y + 1) * (1. - fx) + pixel(x + 1, y + 1) * fx;
+            heights.push((top * (1. - fy) + bottom * fy) / 255.);
+        }
+    }
+
+    Ok(build_grid(&heights, samples, settings))
+}
+
+// heights are row-major, 0..1, `samples` per side
+fn build_grid(heights: &[f32], samples: usize, settings: &TerrainSettings) -> Mesh {
+    let mut mesh: Mesh = Mesh {
+        triangles: Vec::new(),
+    };
+
+    let half = settings.size * 0.5;
+    let step = settings.size / (samples - 1) as f32;
+
+    let vertex = |row: usize, col: usize| {
+        Vector4::new(
+            col as f32 * step - half,
+            heights[row * samples + col] * settings.height_scale,
+            row as f32 * step - half,
+            1.,
+        )
+    };
+
+    for row in 0..samples - 1 {
+        for col in 0..samples - 1 {
+            let a = vertex(row, col);
+            let b = vertex(row, col + 1);
+            let c = vertex(row + 1, col);
+            let d = vertex(row + 1, col + 1);
+
+            // wound so the normals point up, out of the ground
+            for vertices in [[a, c, b], [b, c, d]] {
+                let mut tri: Triangle = Triangle {
+                    vertices,
+                    ..Default::default()
+                };
+                tri.base_color = Some(terrain_color(&tri, settings));
+                mesh.triangles.push(tri);
+            }
+        }
+    }
+
+    mesh
+}
+
+fn terrain_color(triangle: &Triangle, settings: &TerrainSettings) -> SolidSource {
+    match settings.coloring {
+        TerrainColoring::Elevation => {
+            let avg_y =
+                (triangle.vertices[0].y + triangle.vertices[1].y + triangle.vertices[2].y) / 3.;
+            elevation_color(avg_y / settings.height_scale.max(f32::EPSILON))
+        }
+        TerrainColoring::Slope => {
+            // 0 when flat, 1 when vertical
+            let steepness = 1. - derive_normal(triangle).y.abs();
+            slope_color(steepness)
+        }
+    }
+}
+
+// t is the normalized height, 0 at the bottom and 1 at the top
+fn elevation_color(t: f32) -> SolidSource {
+    let bands: [(f32, SolidSource); 5] = [
+        (0.0, rgb(0x2b, 0x4f, 0x81)),  // water
+        (0.3, rgb(0xd8, 0xc8, 0x8c)),  // sand
+        (0.45, rgb(0x5a, 0x8f, 0x3c)), // grass
+        (0.7, rgb(0x7a, 0x6a, 0x5a)),  // rock
+        (0.9, rgb(0xf4, 0xf4, 0xf4)),  // snow
+    ];
+
+    let t = t.clamp(0., 1.);
+    for pair in bands.windows(2) {
+        let (start, low) = pair[0];
+        let (end, high) = pair[1];
+        if t <= end {
+            return mix_colors(low, high, (t - start) / (end - start));
+        }
+    }
+
+    bands[bands.len() - 1].1
+}
+
+fn slope_color(steepness: f32) -> SolidSource {
+    let grass = rgb(0x5a, 0x8f, 0x3c);
+    let rock = rgb(0x7a, 0x6a, 0x5a);
+
+    // hillsides stay green until they get fairly steep
+    mix_colors(grass, rock, (steepness - 0.2) / 0.4)
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> SolidSource {
+    SolidSource { r, g, b, a: 0xff }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heights(seed: u32) -> Vec<f32> {
+        let settings = TerrainSettings {
+            resolution: 8,
+            ..Default::default()
+        };
+        let noise = NoiseSettings {
+            seed,
+            ..Default::default()
+        };
+        let mesh = get_terrain_from_noise(&settings, &noise);
+        assert_eq!(mesh.triangles.len(), 8 * 8 * 2);
+
+        mesh.triangles
+            .iter()
+            .flat_map(|triangle| triangle.vertices.map(|vertex| vertex.y))
+            .collect()
+    }
+
+    #[test]
+    fn seeded_terrain_is_reproducible() {
+        assert_eq!(heights(3), heights(3));
+        assert_ne!(heights(3), heights(4));
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::drawing::overlay::Notice;
+use crate::linear_algebra::data::Mesh;
+use crate::meshes::archive::{list_models, split_archive_path};
+use crate::meshes::initialize_mesh::{load_mesh_tracked, Model};
+use crate::meshes::load_error::LoadError;
+
+// how often the files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// a file's modified time and size at the last poll, None while it's missing
+type Stamp = Option<(SystemTime, u64)>;
+
+// reloads a mesh on a background thread whenever a file it was read from
+// changes, the model itself or any mtl library, texture or buffer it names
+pub struct MeshWatcher {
+    file_path: String,
+    reloads: Receiver<Result<Model, LoadError>>,
+    // dropped along with the watcher, which ends the thread
+    _stop: Sender<()>,
+    // a good reload fades, an error stays until the next one
+    notice: Notice,
+}
+
+impl MeshWatcher {
+    // loads file_path right away and watches it from then on. a broken file
+    // comes back empty, with the error as the notice
+    pub fn load(file_path: &str) -> (Model, MeshWatcher) {
+        // everything else an archive holds, any of it can be opened with
+        // archive.zip#<model>. said once here rather than on every reload
+        if let Some((archive, _)) = split_archive_path(file_path) {
+            if let Ok(models) = list_models(archive) {
+                println!("{}: {}", archive, models.join(", "));
+            }
+        }
+
+        let mut sources: Vec<PathBuf> = Vec::new();
+        let loaded = load_mesh_tracked(file_path, &mut sources);
+        let stamps = sources.iter().map(|source| stamp(source)).collect();
+
+        let (sender, reloads) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel();
+        let path = file_path.to_string();
+        thread::spawn(move || watch(&path, sources, stamps, sender, stopped));
+
+        let mut watcher = MeshWatcher {
+            file_path: file_path.to_string(),
+            reloads,
+            _stop: stop,
+            notice: Notice::default(),
+        };
+        let model = match loaded {
+            Ok(model) => model,
+            Err(e) => {
+                watcher.fail(e);
+                Model::from(Mesh {
+                    triangles: Vec::new(),
+                })
+            }
+        };
+
+        (model, watcher)
+    }
+
+    // the newest mesh loaded since the last call, once a frame. a reload that
+    // failed leaves the current mesh alone and becomes the notice
+    pub fn poll(&mut self) -> Option<Model> {
+        match self.reloads.try_iter().last()? {
+            Ok(model) => {
+                self.notice.show(format!("reloaded {}", self.file_path));
+                Some(model)
+            }
+            Err(e) => {
+                self.fail(e);
+                None
+            }
+        }
+    }
+
+    // what the hud should say about the last load, if anything
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.text()
+    }
+
+    fn fail(&mut self, e: LoadError) {
+        eprintln!("{}", e);
+        self.notice.pin(e.to_string());
+    }
+}
+
+// polls until the watcher is dropped. a change is only reloaded once the files
+// have held still for a poll, so a save in progress isn't read half written
+fn watch(
+    file_path: &str,
+    mut sources: Vec<PathBuf>,
+    mut loaded: Vec<Stamp>,
+    reloads: Sender<Result<Model, LoadError>>,
+    stop: Receiver<()>,
+) {
+    let mut previous = loaded.clone();
+
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(POLL_INTERVAL) {
+        let current: Vec<Stamp> = sources.iter().map(|source| stamp(source)).collect();
+        let settled = current == previous;
+        previous = current.clone();
+        if current == loaded || !settled {
+            continue;
+        }
+
+        let mut reloaded_sources: Vec<PathBuf> = Vec::new();
+        let result = load_mesh_tracked(file_path, &mut reloaded_sources);
+
+        // stamped as they were before loading, so an edit made during it isn't missed
+        loaded = reloaded_sources
+            .iter()
+            .map(|source| match sources.iter().position(|s| s == source) {
+                Some(i) => current[i],
+                None => stamp(source),
+            })
+            .collect();
+        previous = loaded.clone();
+        sources = reloaded_sources;
+
+        if reloads.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+fn stamp(file_path: &Path) -> Stamp {
+    let metadata = fs::metadata(file_path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -0,0 +1,85 @@
+use std::time::Instant;
+
+// a stalled frame (window drag, breakpoint) shouldn't teleport the camera
+const MAX_DELTA: f32 = 0.1;
+
+pub struct Clock {
+    // fixed seconds per frame for deterministic captures, None follows the wall clock
+    pub fixed_step: Option<f32>,
+    // speed of animations, 1 is real time, 0.25 is quarter speed
+    pub time_scale: f32,
+    pub paused: bool,
+    // seconds since the last tick, used for camera movement
+    pub delta: f32,
+    // wall clock seconds since the last tick, unclamped, even in fixed step mode
+    pub frame_time: f32,
+    // seconds of animation to advance this frame, after pausing and scaling
+    pub scaled_delta: f32,
+    // total animation time in seconds
+    pub elapsed: f32,
+    pub frame: u64,
+    last_tick: Instant,
+    step_requested: bool,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            fixed_step: None,
+            time_scale: 1.,
+            paused: false,
+            delta: 0.,
+            frame_time: 0.,
+            scaled_delta: 0.,
+            elapsed: 0.,
+            frame: 0,
+            last_tick: Instant::now(),
+            step_requested: false,
+        }
+    }
+}
+
+impl Clock {
+    // call once at the start of every frame
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let measured = now.duration_since(self.last_tick).as_secs_f32();
+        self.last_tick = now;
+        self.frame_time = measured;
+
+        self.delta = match self.fixed_step {
+            Some(step) => step,
+            None => measured.min(MAX_DELTA),
+        };
+
+        let animating = !self.paused || self.step_requested;
+        self.step_requested = false;
+
+        self.scaled_delta = if animating {
+            self.delta * self.time_scale
+        } else {
+            0.
+        };
+
+        self.elapsed += self.scaled_delta;
+        self.frame += 1;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // advances animations by exactly one frame on the next tick while paused
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step_requested = true;
+    }
+
+    pub fn slower(&mut self) {
+        self.time_scale = (self.time_scale * 0.5).max(1. / 16.);
+    }
+
+    pub fn faster(&mut self) {
+        self.time_scale = (self.time_scale * 2.).min(4.);
+    }
+}